@@ -0,0 +1,109 @@
+//! Alerts for the bot's admin about the health of the AoC session, sent to a channel or as direct
+//! message.
+
+use std::num::NonZeroU64;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use chrono_humanize::Humanize;
+use log::{error, warn};
+use tokio::sync::watch;
+use tokio::time;
+use twilight_http::Client as DiscordClient;
+
+use crate::aoc::Health;
+use crate::metrics;
+use crate::settings::Alerts;
+
+/// Watch the health of the AoC session and alert the bot's admin once the session cookie stops
+/// working. The alert is repeated at the configured interval until the session works again.
+pub async fn run(
+    mut health: watch::Receiver<Health>,
+    discord_client: Arc<DiscordClient>,
+    alerts: Alerts,
+) -> Result<()> {
+    // Opened with the first alert, and retried with the next one if that fails.
+    let mut private_channel_id = None;
+    let mut last_alert: Option<Instant> = None;
+
+    loop {
+        let status = health.borrow().clone();
+
+        let text = match (status.auth_failed_since, last_alert) {
+            (Some(_), Some(at)) if at.elapsed() < alerts.interval => None,
+            (Some(since), _) => {
+                last_alert = Some(Instant::now());
+                Some(format!(
+                    ":warning: The AoC session cookie stopped working {} and the leaderboard \
+                    can't be loaded anymore. The last successful fetch was {}. \
+                    Please renew the cookie.",
+                    since.humanize(),
+                    status
+                        .last_success
+                        .map_or_else(|| "...never".to_owned(), |ts| ts.humanize()),
+                ))
+            }
+            (None, Some(_)) => {
+                last_alert = None;
+                Some(":white_check_mark: The AoC session cookie works again.".to_owned())
+            }
+            (None, None) => None,
+        };
+
+        if let Some(text) = text {
+            warn!("Sending alert: {}", text);
+
+            if let (Some(user_id), None) = (alerts.user_id, private_channel_id) {
+                match private_channel(&discord_client, user_id).await {
+                    Ok(channel_id) => private_channel_id = Some(channel_id),
+                    Err(e) => {
+                        metrics::record_discord_error(&e);
+                        error!("failed opening direct messages with {}: {:?}", user_id, e);
+                    }
+                }
+            }
+
+            for channel_id in alerts.channel_id.into_iter().chain(private_channel_id) {
+                let res = discord_client
+                    .create_message(channel_id.into())
+                    .content(&text)?
+                    .exec()
+                    .await;
+
+                if let Err(e) = res {
+                    metrics::record_discord_http_error(&e);
+                    error!("failed sending alert to {}: {:?}", channel_id, e);
+                }
+            }
+        }
+
+        let next_alert = last_alert.map_or(alerts.interval, |at| {
+            alerts.interval.saturating_sub(at.elapsed())
+        });
+
+        tokio::select! {
+            res = health.changed() => {
+                if res.is_err() {
+                    return Ok(());
+                }
+            }
+            _ = time::sleep(next_alert) => {}
+        }
+    }
+}
+
+/// Open the direct message channel with a user.
+async fn private_channel(
+    discord_client: &DiscordClient,
+    user_id: NonZeroU64,
+) -> Result<NonZeroU64> {
+    let channel = discord_client
+        .create_private_channel(user_id.into())
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    Ok(channel.id.into())
+}
@@ -0,0 +1,168 @@
+//! Validated day and part identifiers of the AoC challenges.
+
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use thiserror::Error;
+
+/// The day of an AoC challenge, guaranteed to be within 1 and 25.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DayNumber(u8);
+
+/// The error for a day number outside of the valid range.
+#[derive(Debug, Error)]
+#[error("day must be between {} and {}", DayNumber::FIRST, DayNumber::LAST)]
+pub struct InvalidDay;
+
+impl DayNumber {
+    /// The first day of an event.
+    pub const FIRST: Self = Self(1);
+    /// The last day of an event.
+    pub const LAST: Self = Self(25);
+
+    /// Create a new day number, if it is within the valid range.
+    pub fn new(day: u8) -> Option<Self> {
+        (Self::FIRST.0..=Self::LAST.0)
+            .contains(&day)
+            .then_some(Self(day))
+    }
+
+    /// The day as plain number.
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    /// Iterate over all days of an event, in order.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (Self::FIRST.0..=Self::LAST.0).map(Self)
+    }
+}
+
+impl TryFrom<u64> for DayNumber {
+    type Error = InvalidDay;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        u8::try_from(value)
+            .ok()
+            .and_then(Self::new)
+            .ok_or(InvalidDay)
+    }
+}
+
+impl FromStr for DayNumber {
+    type Err = InvalidDay;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<u64>()
+            .map_err(|_| InvalidDay)
+            .and_then(Self::try_from)
+    }
+}
+
+impl Display for DayNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for DayNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // AoC uses the day as key in a JSON object, so it has to be a string.
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DayNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DayVisitor;
+
+        impl<'de> Visitor<'de> for DayVisitor {
+            type Value = DayNumber;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a day number between 1 and 25")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                DayNumber::try_from(v).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(DayVisitor)
+    }
+}
+
+/// One of the two parts of each day's challenge. Solving a part earns one star.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub enum Part {
+    #[serde(rename = "1")]
+    One,
+    #[serde(rename = "2")]
+    Two,
+}
+
+impl Part {
+    /// Both parts, in order.
+    pub const ALL: [Self; 2] = [Self::One, Self::Two];
+
+    /// The part as plain number.
+    pub fn get(self) -> u8 {
+        match self {
+            Self::One => 1,
+            Self::Two => 2,
+        }
+    }
+}
+
+impl Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn days_deserialize_from_map_keys() {
+        let days =
+            serde_json::from_str::<BTreeMap<DayNumber, u8>>(r#"{"1": 1, "25": 25}"#).unwrap();
+
+        assert_eq!(
+            days.keys().map(|day| day.get()).collect::<Vec<_>>(),
+            [1, 25]
+        );
+        assert_eq!(serde_json::to_string(&days).unwrap(), r#"{"1":1,"25":25}"#);
+    }
+
+    #[test]
+    fn days_deserialize_from_numbers() {
+        assert_eq!(
+            serde_json::from_str::<DayNumber>("7").unwrap(),
+            DayNumber::new(7).unwrap()
+        );
+    }
+
+    #[test]
+    fn days_outside_of_the_event_are_rejected() {
+        for day in ["0", "26", "\"0\"", "\"26\"", "\"first\"", "-1"] {
+            assert!(
+                serde_json::from_str::<DayNumber>(day).is_err(),
+                "{} was accepted",
+                day
+            );
+        }
+
+        assert!(serde_json::from_str::<BTreeMap<DayNumber, u8>>(r#"{"26": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Comparison of two leaderboard snapshots, to find out what happened in between.
+
+use chrono::prelude::*;
+
+use super::{DayNumber, LeaderboardStats, Part, User};
+
+/// A single change between two snapshots of the same leaderboard.
+#[derive(Debug)]
+pub enum Change<'a> {
+    /// A new member joined the leaderboard.
+    Joined(&'a User),
+    /// A member left the leaderboard.
+    Left(&'a User),
+    /// A member earned a star by solving a part of a day's challenge.
+    Star {
+        member: &'a User,
+        day: DayNumber,
+        part: Part,
+        earned_at: DateTime<Utc>,
+    },
+}
+
+/// Find all changes between an older and a newer snapshot of a leaderboard. Members joining or
+/// leaving come first, followed by the new stars in the order they were earned.
+pub fn diff<'a>(old: &'a LeaderboardStats, new: &'a LeaderboardStats) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+    let mut stars = Vec::new();
+
+    for (id, member) in &new.members {
+        let old_member = match old.members.get(id) {
+            Some(old_member) => old_member,
+            None => {
+                changes.push(Change::Joined(member));
+                continue;
+            }
+        };
+
+        for (day, part, earned_at) in member.all_star_times() {
+            if old_member
+                .stars_on(day)
+                .all(|(old_part, _)| old_part != part)
+            {
+                stars.push(Change::Star {
+                    member,
+                    day,
+                    part,
+                    earned_at,
+                });
+            }
+        }
+    }
+
+    changes.extend(
+        old.members
+            .iter()
+            .filter(|(id, _)| !new.members.contains_key(*id))
+            .map(|(_, member)| Change::Left(member)),
+    );
+
+    stars.sort_by_key(|change| match change {
+        Change::Star { earned_at, .. } => Some(*earned_at),
+        _ => None,
+    });
+    changes.extend(stars);
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::testing::{member, stats};
+
+    /// Describe a change in a way that is easy to compare.
+    fn describe(change: &Change<'_>) -> String {
+        match change {
+            Change::Joined(member) => format!("joined {}", member.id),
+            Change::Left(member) => format!("left {}", member.id),
+            Change::Star {
+                member, day, part, ..
+            } => format!("star {} {}/{}", member.id, day, part),
+        }
+    }
+
+    fn changes(old: &LeaderboardStats, new: &LeaderboardStats) -> Vec<String> {
+        diff(old, new).iter().map(describe).collect()
+    }
+
+    #[test]
+    fn unchanged_members_have_no_changes() {
+        let old = stats([
+            member("a")
+                .star(1, Part::One, "2020-12-01T05:10:00Z")
+                .build(),
+            member("b").build(),
+        ]);
+        let new = old.clone();
+
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn new_stars_are_ordered_by_time() {
+        let old = stats([
+            member("a")
+                .star(1, Part::One, "2020-12-01T05:10:00Z")
+                .build(),
+            member("b").build(),
+        ]);
+        let new = stats([
+            member("a")
+                .star(1, Part::One, "2020-12-01T05:10:00Z")
+                .star(1, Part::Two, "2020-12-01T05:30:00Z")
+                .build(),
+            member("b")
+                .star(1, Part::One, "2020-12-01T05:20:00Z")
+                .star(2, Part::One, "2020-12-02T05:05:00Z")
+                .build(),
+        ]);
+
+        assert_eq!(
+            changes(&old, &new),
+            ["star b 1/1", "star a 1/2", "star b 2/1"]
+        );
+    }
+
+    #[test]
+    fn joining_and_leaving_come_before_stars() {
+        let old = stats([
+            member("stays").build(),
+            member("leaves")
+                .star(1, Part::One, "2020-12-01T05:10:00Z")
+                .build(),
+        ]);
+        let new = stats([
+            member("stays")
+                .star(1, Part::One, "2020-12-01T05:20:00Z")
+                .build(),
+            // Stars that members had before joining aren't announced.
+            member("joins")
+                .star(1, Part::One, "2020-12-01T05:05:00Z")
+                .build(),
+        ]);
+
+        assert_eq!(
+            changes(&old, &new),
+            ["joined joins", "left leaves", "star stays 1/1"]
+        );
+    }
+
+    #[test]
+    fn members_that_leave_and_join_again() {
+        let old = stats([member("a")
+            .star(1, Part::One, "2020-12-01T05:10:00Z")
+            .build()]);
+        let gone = stats([]);
+
+        assert_eq!(changes(&old, &gone), ["left a"]);
+        assert_eq!(changes(&gone, &old), ["joined a"]);
+    }
+}
@@ -0,0 +1,61 @@
+//! Errors that can occur while talking to the AoC API.
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The session cookie is not accepted anymore, usually because it expired. AoC redirects to
+    /// the login page or responds with an HTML page instead of JSON data in that case.
+    #[error("the AoC session cookie is invalid or expired")]
+    SessionExpired,
+    /// The requested leaderboard doesn't exist or isn't accessible with the current session.
+    #[error("the leaderboard doesn't exist")]
+    BoardNotFound,
+    /// The requested puzzle doesn't exist or isn't unlocked yet.
+    #[error("the puzzle isn't available")]
+    PuzzleNotFound,
+    /// The AoC website has issues and responded with a server error.
+    #[error("the AoC server responded with {0}")]
+    ServerError(StatusCode),
+    /// Any other unexpected status code in the response.
+    #[error("unexpected response status {0}")]
+    UnexpectedStatus(StatusCode),
+    /// The response couldn't be parsed, likely because the JSON schema changed.
+    #[error("the response has an unexpected format")]
+    InvalidResponse(#[source] serde_json::Error),
+    /// A request isn't allowed yet to respect the rate limit and there is no previous response
+    /// that could be served instead.
+    #[error("request throttled, next one allowed in {}", humantime::format_duration(*.retry_in))]
+    Throttled { retry_in: Duration },
+    /// The session cookie contains characters that are not allowed in an HTTP header.
+    #[error("the session cookie contains invalid characters")]
+    InvalidCookie(#[from] reqwest::header::InvalidHeaderValue),
+    /// The request failed to be sent or the response couldn't be received.
+    #[error("failed sending request")]
+    Request(#[from] reqwest::Error),
+}
+
+impl Error {
+    /// Whether the error is caused by missing or invalid authentication.
+    pub fn is_auth(&self) -> bool {
+        matches!(self, Self::SessionExpired | Self::InvalidCookie(_))
+    }
+
+    /// Short name of the error type, for example to label metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SessionExpired => "session_expired",
+            Self::BoardNotFound => "board_not_found",
+            Self::PuzzleNotFound => "puzzle_not_found",
+            Self::ServerError(_) => "server_error",
+            Self::UnexpectedStatus(_) => "unexpected_status",
+            Self::InvalidResponse(_) => "invalid_response",
+            Self::Throttled { .. } => "throttled",
+            Self::InvalidCookie(_) => "invalid_cookie",
+            Self::Request(_) => "request",
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Rate limiting for requests against the AoC website, to stay within the limits asked for by its
+//! owners, regardless of how often the bot itself is asked for statistics.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use tokio::sync::watch;
+
+/// The minimum interval between two requests to the same endpoint, as asked by the AoC website
+/// owners. Any configured interval below this value is raised to it, unless the server runs on the
+/// same machine.
+pub const MIN_REQUEST_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Last request time of each endpoint.
+pub type RequestTimes = HashMap<String, DateTime<Utc>>;
+
+/// Keeps track of the last request time for each endpoint and decides whether another request to
+/// it is allowed yet.
+pub(super) struct Governor {
+    interval: Duration,
+    last_requests: Mutex<RequestTimes>,
+    /// Receives all request times after each change, so they can be persisted.
+    changes: watch::Sender<RequestTimes>,
+}
+
+impl Governor {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_requests: Mutex::default(),
+            changes: watch::channel(RequestTimes::new()).0,
+        }
+    }
+
+    /// The minimum time between two requests to the same endpoint.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Subscribe to the request times, which are updated whenever they change.
+    pub fn subscribe(&self) -> watch::Receiver<RequestTimes> {
+        self.changes.subscribe()
+    }
+
+    /// Try to reserve a request to the given endpoint. If allowed, the current time is recorded as
+    /// the last request time and the previous one is returned, so the reservation can be
+    /// [released](Self::release) again. Otherwise, the remaining time until the next allowed
+    /// request is returned.
+    pub fn acquire(&self, endpoint: &str) -> Result<Option<DateTime<Utc>>, Duration> {
+        let now = Utc::now();
+        let mut last_requests = self.last_requests.lock().unwrap();
+
+        if let Some(last) = last_requests.get(endpoint) {
+            let next = *last
+                + chrono::Duration::from_std(self.interval)
+                    .unwrap_or_else(|_| chrono::Duration::max_value());
+            if next > now {
+                return Err(Duration::from_secs((next - now).num_seconds().max(0) as u64));
+            }
+        }
+
+        let previous = last_requests.insert(endpoint.to_owned(), now);
+        self.changes.send_replace(last_requests.clone());
+
+        Ok(previous)
+    }
+
+    /// Give back a reservation for a request that never reached the server, restoring the
+    /// previous last request time.
+    pub fn release(&self, endpoint: &str, previous: Option<DateTime<Utc>>) {
+        let mut last_requests = self.last_requests.lock().unwrap();
+
+        match previous {
+            Some(previous) => last_requests.insert(endpoint.to_owned(), previous),
+            None => last_requests.remove(endpoint),
+        };
+        self.changes.send_replace(last_requests.clone());
+    }
+
+    /// Record a request to the endpoint that happened at the given time, for example before the
+    /// last restart of the bot. Newer records are kept.
+    pub fn record(&self, endpoint: &str, at: DateTime<Utc>) {
+        let mut last_requests = self.last_requests.lock().unwrap();
+        let last = last_requests.entry(endpoint.to_owned()).or_insert(at);
+
+        if *last < at {
+            *last = at;
+        }
+        self.changes.send_replace(last_requests.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENDPOINT: &str = "https://adventofcode.com/2020/leaderboard/private/view/1.json";
+
+    fn governor() -> Governor {
+        Governor::new(MIN_REQUEST_INTERVAL)
+    }
+
+    #[test]
+    fn only_the_first_request_within_the_interval_is_allowed() {
+        let governor = governor();
+
+        assert_eq!(governor.acquire(ENDPOINT), Ok(None));
+
+        let retry_in = governor.acquire(ENDPOINT).unwrap_err();
+        assert!(retry_in > MIN_REQUEST_INTERVAL - Duration::from_secs(5));
+        assert!(retry_in <= MIN_REQUEST_INTERVAL);
+
+        // Other endpoints have their own limit.
+        assert_eq!(
+            governor.acquire("https://adventofcode.com/2020/day/1"),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn releasing_restores_the_previous_request() {
+        let governor = governor();
+        let earlier = Utc::now() - chrono::Duration::hours(1);
+
+        governor.release(ENDPOINT, governor.acquire(ENDPOINT).unwrap());
+        assert_eq!(governor.acquire(ENDPOINT), Ok(None));
+
+        governor.release(ENDPOINT, Some(earlier));
+        assert_eq!(governor.acquire(ENDPOINT), Ok(Some(earlier)));
+    }
+
+    #[test]
+    fn records_keep_the_latest_request() {
+        let governor = governor();
+        let recent = Utc::now() - chrono::Duration::minutes(5);
+
+        governor.record(ENDPOINT, recent);
+        governor.record(ENDPOINT, recent - chrono::Duration::hours(1));
+
+        let retry_in = governor.acquire(ENDPOINT).unwrap_err();
+        assert!(retry_in > Duration::from_secs(9 * 60));
+        assert!(retry_in <= Duration::from_secs(10 * 60));
+
+        governor.record(ENDPOINT, Utc::now() - chrono::Duration::hours(1));
+        assert!(governor.acquire(ENDPOINT).is_err());
+    }
+
+    #[test]
+    fn requests_are_still_throttled_after_a_restart() {
+        let before = governor();
+        let changes = before.subscribe();
+
+        // The request counts even if it fails afterwards, as it reached the server.
+        before.acquire(ENDPOINT).unwrap();
+
+        let after = governor();
+        for (endpoint, at) in changes.borrow().iter() {
+            after.record(endpoint, *at);
+        }
+
+        assert!(after.acquire(ENDPOINT).is_err());
+    }
+
+    #[test]
+    fn changes_are_published() {
+        let governor = governor();
+        let changes = governor.subscribe();
+
+        let previous = governor.acquire(ENDPOINT).unwrap();
+        assert!(changes.borrow().contains_key(ENDPOINT));
+
+        governor.release(ENDPOINT, previous);
+        assert!(changes.borrow().is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+//! Health tracking for the AoC session, to notice when the session cookie stops working.
+
+use chrono::prelude::*;
+
+use super::Error;
+
+/// Current health of the AoC session, as observed from the responses to the client's requests.
+#[derive(Clone, Debug, Default)]
+pub struct Health {
+    /// Time of the last successful request.
+    pub last_success: Option<DateTime<Utc>>,
+    /// Time of the first request that failed due to invalid authentication, since the last
+    /// successful one. Not set as long as the session works.
+    pub auth_failed_since: Option<DateTime<Utc>>,
+}
+
+impl Health {
+    /// Whether the session was accepted by AoC on the last request.
+    pub fn is_authenticated(&self) -> bool {
+        self.auth_failed_since.is_none()
+    }
+
+    /// Update the health with the outcome of a request.
+    pub(super) fn record<T>(&mut self, result: &Result<T, Error>) {
+        let now = Utc::now();
+
+        match result {
+            Ok(_) => {
+                self.last_success = Some(now);
+                self.auth_failed_since = None;
+            }
+            Err(e) if e.is_auth() => {
+                self.auth_failed_since.get_or_insert(now);
+            }
+            Err(_) => {}
+        }
+    }
+}
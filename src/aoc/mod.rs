@@ -1,81 +1,655 @@
 //! Advent of Code API to retrieve leaderboard statistics.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use anyhow::Result;
 use chrono::prelude::*;
+use log::{debug, warn};
 use reqwest::header::{self, HeaderMap};
-use serde::Deserialize;
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use tokio::sync::watch;
 
-#[derive(Clone, Debug, Deserialize)]
+use crate::metrics;
+
+pub use self::day::{DayNumber, InvalidDay, Part};
+pub use self::error::Error;
+use self::governor::Governor;
+pub use self::governor::{RequestTimes, MIN_REQUEST_INTERVAL};
+pub use self::health::Health;
+
+mod day;
+pub mod diff;
+mod error;
+mod governor;
+mod health;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Address of the official AoC website.
+pub const DEFAULT_BASE_URL: &str = "https://adventofcode.com";
+
+/// Year of the very first AoC event.
+pub const FIRST_EVENT_YEAR: u16 = 2015;
+
+/// The timezone of the AoC event. All puzzles unlock at midnight EST (UTC-5).
+pub fn event_timezone() -> FixedOffset {
+    FixedOffset::west(5 * 3600)
+}
+
+/// Get the time at which the puzzle of a single day unlocks.
+pub fn unlock_time(year: u16, day: DayNumber) -> DateTime<Utc> {
+    event_timezone()
+        .ymd(year.into(), 12, day.get().into())
+        .and_hms(0, 0, 0)
+        .with_timezone(&Utc)
+}
+
+/// Get the time at which the event of a year is over, which is at the end of December.
+pub fn event_end(year: u16) -> DateTime<Utc> {
+    event_timezone()
+        .ymd(i32::from(year) + 1, 1, 1)
+        .and_hms(0, 0, 0)
+        .with_timezone(&Utc)
+}
+
+/// Get the year of the most recent AoC event that already started. Events start on the 1st of
+/// December, at midnight EST (UTC-5).
+pub fn latest_event_year() -> u16 {
+    let now = Utc::now().with_timezone(&event_timezone());
+    let year = if now.month() == 12 {
+        now.year()
+    } else {
+        now.year() - 1
+    };
+
+    year as u16
+}
+
+/// Check whether an AoC event was held in the given year.
+pub fn is_event_year(year: u16) -> bool {
+    (FIRST_EVENT_YEAR..=latest_event_year()).contains(&year)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct LeaderboardStats {
     pub event: String,
     pub owner_id: String,
     pub members: HashMap<String, User>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct User {
     pub id: String,
     pub name: Option<String>,
     pub stars: u32,
     pub local_score: u32,
     pub global_score: u32,
-    pub completion_day_level: HashMap<String, Day>,
+    /// Time of the most recently earned star, if any.
+    #[serde(default, with = "optional_ts_seconds")]
+    pub last_star_ts: Option<DateTime<Utc>>,
+    pub completion_day_level: BTreeMap<DayNumber, Day>,
+}
+
+impl User {
+    /// Name of the member as shown on the AoC website, which may be anonymous.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous>")
+    }
+
+    /// Iterate over the stars earned on a single day, ordered by part.
+    pub fn stars_on(&self, day: DayNumber) -> impl Iterator<Item = (Part, &Challenge)> {
+        self.completion_day_level
+            .get(&day)
+            .into_iter()
+            .flat_map(Day::iter)
+    }
+
+    /// Iterate over the completion times of all earned stars, ordered by day and part.
+    pub fn all_star_times(&self) -> impl Iterator<Item = (DayNumber, Part, DateTime<Utc>)> + '_ {
+        self.completion_day_level.iter().flat_map(|(&day, level)| {
+            level
+                .iter()
+                .map(move |(part, challenge)| (day, part, challenge.get_star_ts))
+        })
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// The completed parts of a single day's challenge.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct Day {
-    #[serde(rename = "1")]
-    pub part1: Challenge,
-    #[serde(rename = "2")]
-    pub part2: Option<Challenge>,
+    pub parts: BTreeMap<Part, Challenge>,
+}
+
+impl Day {
+    /// Get the completion of a single part, if it was solved.
+    pub fn get(&self, part: Part) -> Option<&Challenge> {
+        self.parts.get(&part)
+    }
+
+    /// Iterate over the solved parts, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (Part, &Challenge)> {
+        self.parts
+            .iter()
+            .map(|(&part, challenge)| (part, challenge))
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Challenge {
     #[serde(with = "chrono::serde::ts_seconds")]
     pub get_star_ts: DateTime<Utc>,
+    /// Global, increasing index of the star, which orders stars that were earned within the same
+    /// second.
+    #[serde(default)]
+    pub star_index: u64,
+}
+
+/// Whether the URL points to the local machine. Any other host could be the AoC website, under
+/// whatever spelling.
+fn is_loopback(url: &str) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    match url.host_str() {
+        Some(host) => {
+            host.eq_ignore_ascii_case("localhost")
+                || host
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+                    .is_ok_and(|ip| ip.is_loopback())
+        }
+        None => false,
+    }
+}
+
+/// Timestamps in seconds, where `0` means no value. Besides numbers, AoC used strings for these
+/// timestamps in the past.
+mod optional_ts_seconds {
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timestamp {
+        Number(i64),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ts = match Timestamp::deserialize(deserializer)? {
+            Timestamp::Number(ts) => ts,
+            Timestamp::Text(ts) => ts.parse().map_err(serde::de::Error::custom)?,
+        };
+
+        Ok((ts > 0).then(|| Utc.timestamp(ts, 0)))
+    }
+
+    pub fn serialize<S>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i64(value.map_or(0, |ts| ts.timestamp()))
+    }
+}
+
+/// Data retrieved from the AoC API, together with the time it was fetched.
+#[derive(Clone, Debug)]
+pub struct Fetched<T> {
+    pub data: T,
+    pub fetched_at: DateTime<Utc>,
+    /// Whether the data was just fetched, or a previous response is re-used because the rate limit
+    /// for the endpoint didn't allow a new request yet.
+    pub fresh: bool,
 }
 
 #[derive(Clone)]
 pub struct Client {
     http: reqwest::Client,
+    base_url: Arc<str>,
+    governor: Arc<Governor>,
+    /// Last successful responses per endpoint, to serve stale data while requests are throttled.
+    responses: Arc<Mutex<HashMap<String, Fetched<LeaderboardStats>>>>,
+    health: Arc<watch::Sender<Health>>,
 }
 
 impl Client {
-    pub fn new(session_cookie: &str) -> Result<Self> {
+    /// Create a new client, that authenticates with the given session cookie and sends at most one
+    /// request per endpoint within the given interval. All requests are sent to the given base URL,
+    /// which is usually [`DEFAULT_BASE_URL`]. The interval is raised to [`MIN_REQUEST_INTERVAL`] if
+    /// lower, unless the server runs on the same machine, like the mock server.
+    pub fn new(session_cookie: &str, base_url: &str, request_interval: Duration) -> Result<Self> {
         let cookie = format!("session={}", session_cookie);
 
         let mut headers = HeaderMap::with_capacity(1);
         headers.insert(header::COOKIE, cookie.try_into()?);
 
+        let base_url = base_url.trim_end_matches('/');
+        let request_interval = if !is_loopback(base_url) && request_interval < MIN_REQUEST_INTERVAL
+        {
+            warn!(
+                "AoC request interval of {} is too low, using {} instead",
+                humantime::format_duration(request_interval),
+                humantime::format_duration(MIN_REQUEST_INTERVAL)
+            );
+            MIN_REQUEST_INTERVAL
+        } else {
+            request_interval
+        };
+
         Ok(Self {
             http: reqwest::Client::builder()
                 .default_headers(headers)
+                // AoC redirects to the login page if the session is invalid. Disable redirects to
+                // be able to detect this case.
+                .redirect(Policy::none())
                 .build()?,
+            base_url: Arc::from(base_url),
+            governor: Arc::new(Governor::new(request_interval)),
+            responses: Arc::default(),
+            health: Arc::new(watch::channel(Health::default()).0),
         })
     }
 
+    /// The minimum time between two requests to the same endpoint.
+    pub fn request_interval(&self) -> Duration {
+        self.governor.interval()
+    }
+
+    /// Subscribe to the health of the AoC session, which is updated after each request.
+    pub fn health(&self) -> watch::Receiver<Health> {
+        self.health.subscribe()
+    }
+
+    /// Subscribe to the last request time of each endpoint, which is updated with every request,
+    /// no matter whether it succeeded.
+    pub fn request_times(&self) -> watch::Receiver<RequestTimes> {
+        self.governor.subscribe()
+    }
+
+    /// Let the client know about a request that was sent earlier, for example before a restart. It
+    /// counts towards the rate limit of the endpoint.
+    pub fn record_request(&self, endpoint: &str, requested_at: DateTime<Utc>) {
+        self.governor.record(endpoint, requested_at);
+    }
+
+    /// Let the client know about leaderboard statistics that were fetched earlier, for example
+    /// before a restart. They count towards the rate limit and are served while it is in effect.
+    pub fn prime(
+        &self,
+        event: u16,
+        leaderboard_id: &str,
+        fetched_at: DateTime<Utc>,
+        stats: LeaderboardStats,
+    ) {
+        let url = self.private_leaderboard_url(event, leaderboard_id);
+        self.governor.record(&url, fetched_at);
+
+        self.health.send_modify(|health| {
+            if !matches!(health.last_success, Some(last) if last >= fetched_at) {
+                health.last_success = Some(fetched_at);
+            }
+        });
+
+        let mut responses = self.responses.lock().unwrap();
+        let newer = !matches!(
+            responses.get(&url),
+            Some(existing) if existing.fetched_at >= fetched_at
+        );
+
+        if newer {
+            responses.insert(
+                url,
+                Fetched {
+                    data: stats,
+                    fetched_at,
+                    fresh: false,
+                },
+            );
+        }
+    }
+
     /// Get the latest statistics from a private leaderboard. It is asked by the AoC website owners
-    /// to not request this data more often than every 15 minutes.
+    /// to not request this data more often than every 15 minutes. Therefore, the last response is
+    /// returned instead if the request interval didn't pass yet, or an error if there is none.
     pub async fn get_private_leaderboard_stats(
         &self,
         event: u16,
         leaderboard_id: &str,
-    ) -> Result<LeaderboardStats> {
-        let url = format!(
-            "https://adventofcode.com/{}/leaderboard/private/view/{}.json",
-            event, leaderboard_id
+    ) -> Result<Fetched<LeaderboardStats>> {
+        let url = self.private_leaderboard_url(event, leaderboard_id);
+
+        let previous = match self.governor.acquire(&url) {
+            Ok(previous) => previous,
+            Err(retry_in) => {
+                if let Some(stale) = self.responses.lock().unwrap().get(&url) {
+                    debug!("request to {} throttled, serving previous response", url);
+                    return Ok(Fetched {
+                        fresh: false,
+                        ..stale.clone()
+                    });
+                }
+
+                return Err(Error::Throttled { retry_in });
+            }
+        };
+
+        let result = observe("leaderboard", self.get_json(&url)).await;
+        self.release_unsent(&url, previous, &result);
+        self.health.send_modify(|health| health.record(&result));
+        let stats = result?;
+
+        let fetched = Fetched {
+            data: stats,
+            fetched_at: Utc::now(),
+            fresh: true,
+        };
+
+        self.responses.lock().unwrap().insert(url, fetched.clone());
+
+        Ok(fetched)
+    }
+
+    /// Get the title of a day's puzzle, by extracting it from the puzzle page. The page is only
+    /// available once the puzzle unlocked.
+    pub async fn get_puzzle_title(&self, event: u16, day: DayNumber) -> Result<Option<String>> {
+        let url = self.puzzle_url(event, day);
+
+        let previous = self
+            .governor
+            .acquire(&url)
+            .map_err(|retry_in| Error::Throttled { retry_in })?;
+
+        let result = observe("puzzle", async {
+            Ok(self.send(&url, Error::PuzzleNotFound).await?.text().await?)
+        })
+        .await;
+        self.release_unsent(&url, previous, &result);
+
+        Ok(extract_puzzle_title(&result?))
+    }
+
+    /// Release the rate limit of a request that failed before connecting to the server, like for
+    /// DNS errors. It didn't count for the server, so there's no need to wait before retrying.
+    fn release_unsent<T>(&self, url: &str, previous: Option<DateTime<Utc>>, result: &Result<T>) {
+        if matches!(result, Err(Error::Request(e)) if e.is_connect()) {
+            debug!(
+                "request to {} failed before connecting, releasing rate limit",
+                url
+            );
+            self.governor.release(url, previous);
+        }
+    }
+
+    /// Link to the puzzle page of a single day.
+    pub fn puzzle_url(&self, event: u16, day: DayNumber) -> String {
+        format!("{}/{}/day/{}", self.base_url, event, day)
+    }
+
+    fn private_leaderboard_url(&self, event: u16, leaderboard_id: &str) -> String {
+        format!(
+            "{}/{}/leaderboard/private/view/{}.json",
+            self.base_url, event, leaderboard_id
+        )
+    }
+
+    /// Send a request to the given URL and parse the JSON response, while detecting the different
+    /// failure cases of the AoC API.
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let resp = self.send(url, Error::BoardNotFound).await?;
+
+        let is_html = resp
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        if is_html {
+            return Err(Error::SessionExpired);
+        }
+
+        let body = resp.bytes().await?;
+        serde_json::from_slice(&body).map_err(Error::InvalidResponse)
+    }
+
+    /// Send a request to the given URL and check the response status. The `not_found` error is
+    /// returned for a `404` status, as its meaning depends on the requested resource.
+    async fn send(&self, url: &str, not_found: Error) -> Result<reqwest::Response> {
+        let resp = self.http.get(url).send().await?;
+        let status = resp.status();
+
+        if status.is_redirection() {
+            return Err(Error::SessionExpired);
+        }
+        if status == StatusCode::NOT_FOUND {
+            return Err(not_found);
+        }
+        if status.is_server_error() {
+            return Err(Error::ServerError(status));
+        }
+        if !status.is_success() {
+            return Err(Error::UnexpectedStatus(status));
+        }
+
+        Ok(resp)
+    }
+}
+
+/// Measure the duration of a request to the given endpoint and count its errors.
+async fn observe<T>(endpoint: &str, request: impl Future<Output = Result<T>>) -> Result<T> {
+    let timer = metrics::AOC_REQUEST_DURATION
+        .with_label_values(&[endpoint])
+        .start_timer();
+    let result = request.await;
+    timer.observe_duration();
+
+    if let Err(e) = &result {
+        metrics::AOC_ERRORS
+            .with_label_values(&[endpoint, e.kind()])
+            .inc();
+    }
+
+    result
+}
+
+/// Find the puzzle title in the HTML of a puzzle page. It is located in the first heading, which has
+/// the format `--- Day 1: The Title ---`.
+fn extract_puzzle_title(page: &str) -> Option<String> {
+    let start = page.find("<h2>--- Day ")?;
+    let (heading, _) = page[start..].split_once("</h2>")?;
+    let (_, title) = heading.strip_suffix(" ---")?.split_once(": ")?;
+
+    Some(
+        title
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Statistics as returned by the AoC website, with one member still using a string for the
+    /// time of the last star, like AoC did in earlier years.
+    const STATS: &str = r#"{
+        "event": "2021",
+        "owner_id": "1234",
+        "members": {
+            "1234": {
+                "id": "1234",
+                "name": "Ada Lovelace",
+                "stars": 3,
+                "local_score": 8,
+                "global_score": 0,
+                "last_star_ts": 1638425062,
+                "completion_day_level": {
+                    "1": {
+                        "1": {"get_star_ts": 1638336145, "star_index": 17},
+                        "2": {"get_star_ts": 1638336600, "star_index": 4321}
+                    },
+                    "2": {
+                        "1": {"get_star_ts": 1638425062, "star_index": 912345}
+                    }
+                }
+            },
+            "5678": {
+                "id": "5678",
+                "name": null,
+                "stars": 1,
+                "local_score": 2,
+                "global_score": 0,
+                "last_star_ts": "1638338000",
+                "completion_day_level": {
+                    "1": {
+                        "1": {"get_star_ts": 1638338000}
+                    }
+                }
+            },
+            "9012": {
+                "id": "9012",
+                "name": "Alan Turing",
+                "stars": 0,
+                "local_score": 0,
+                "global_score": 0,
+                "last_star_ts": 0,
+                "completion_day_level": {}
+            }
+        }
+    }"#;
+
+    fn stats() -> LeaderboardStats {
+        serde_json::from_str(STATS).unwrap()
+    }
+
+    #[test]
+    fn days_and_parts_are_read_from_string_keys() {
+        let stats = stats();
+        let ada = &stats.members["1234"];
+
+        let stars = ada
+            .all_star_times()
+            .map(|(day, part, earned_at)| (day.get(), part, earned_at.timestamp()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stars,
+            [
+                (1, Part::One, 1638336145),
+                (1, Part::Two, 1638336600),
+                (2, Part::One, 1638425062),
+            ]
+        );
+
+        let day = DayNumber::new(1).unwrap();
+        assert_eq!(
+            ada.stars_on(day)
+                .map(|(_, c)| c.star_index)
+                .collect::<Vec<_>>(),
+            [17, 4321]
+        );
+        assert_eq!(
+            stats.members["5678"]
+                .stars_on(day)
+                .next()
+                .unwrap()
+                .1
+                .star_index,
+            0
+        );
+    }
+
+    #[test]
+    fn last_star_times_are_read_from_numbers_and_strings() {
+        let stats = stats();
+
+        assert_eq!(
+            stats.members["1234"].last_star_ts,
+            Some(Utc.timestamp(1638425062, 0))
         );
+        assert_eq!(
+            stats.members["5678"].last_star_ts,
+            Some(Utc.timestamp(1638338000, 0))
+        );
+        assert_eq!(stats.members["9012"].last_star_ts, None);
+        assert_eq!(stats.members["5678"].display_name(), "<anonymous>");
+    }
+
+    #[test]
+    fn stats_survive_a_round_trip() {
+        // The store keeps snapshots as JSON, so they have to read back the same way.
+        let stats = stats();
+        let stored = serde_json::to_string(&serde_json::to_value(&stats).unwrap()).unwrap();
+        let restored = serde_json::from_str::<LeaderboardStats>(&stored).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&stats).unwrap()
+        );
+        assert_eq!(restored.members["9012"].last_star_ts, None);
+        assert_eq!(
+            restored.members["5678"].last_star_ts,
+            Some(Utc.timestamp(1638338000, 0))
+        );
+    }
+
+    #[test]
+    fn puzzle_titles_are_extracted_from_the_page() {
+        let page = r#"<!DOCTYPE html>
+<html lang="en-us">
+<head>
+<meta charset="utf-8"/>
+<title>Day 7 - Advent of Code 2015</title>
+</head><!--
+-->
+<body>
+<header><div><h1 class="title-global"><a href="/">Advent of Code</a></h1></div></header>
+<main>
+<article class="day-desc"><h2>--- Day 7: Some Assembly Required ---</h2><p>This year, Santa brought little Bobby Tables a set of wires and <a href="https://en.wikipedia.org/wiki/Bitwise_operation" target="_blank">bitwise logic gates</a>!</p>
+<h2 id="part2">--- Part Two ---</h2>
+</article>
+</main>
+</body>
+</html>"#;
+
+        assert_eq!(
+            extract_puzzle_title(page).as_deref(),
+            Some("Some Assembly Required")
+        );
+    }
+
+    #[test]
+    fn html_entities_in_puzzle_titles_are_decoded() {
+        let page = "<article><h2>--- Day 4: Rock &amp; &lt;Roll&gt; &quot;Don&#39;t&quot; ---</h2>";
+
+        assert_eq!(
+            extract_puzzle_title(page).as_deref(),
+            Some(r#"Rock & <Roll> "Don't""#)
+        );
+    }
+
+    #[test]
+    fn malformed_headings_have_no_title() {
+        // A separator after the end of the heading must not be picked up.
+        let page = "<h2>--- Day 1 ---</h2><p>Note: this has no title --- at all</p>";
+        assert_eq!(extract_puzzle_title(page), None);
 
-        self.http
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await
-            .map_err(Into::into)
+        assert_eq!(extract_puzzle_title("<h2>--- Day 1: Unclosed ---"), None);
+        assert_eq!(extract_puzzle_title("<p>Please log in.</p>"), None);
     }
 }
@@ -0,0 +1,76 @@
+//! Builders for leaderboard statistics in unit tests.
+
+use chrono::prelude::*;
+
+use super::{Challenge, DayNumber, LeaderboardStats, Part, User};
+
+/// Parse a time in RFC 3339 format.
+pub fn ts(time: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(time)
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+/// Statistics of a 2020 leaderboard with the given members.
+pub fn stats(members: impl IntoIterator<Item = User>) -> LeaderboardStats {
+    LeaderboardStats {
+        event: "2020".to_owned(),
+        owner_id: "1".to_owned(),
+        members: members
+            .into_iter()
+            .map(|member| (member.id.clone(), member))
+            .collect(),
+    }
+}
+
+/// Start building a member, that is named after its ID.
+pub fn member(id: &str) -> MemberBuilder {
+    MemberBuilder(User {
+        id: id.to_owned(),
+        name: Some(id.to_owned()),
+        stars: 0,
+        local_score: 0,
+        global_score: 0,
+        last_star_ts: None,
+        completion_day_level: Default::default(),
+    })
+}
+
+pub struct MemberBuilder(User);
+
+impl MemberBuilder {
+    pub fn local_score(mut self, local_score: u32) -> Self {
+        self.0.local_score = local_score;
+        self
+    }
+
+    /// Add a star that was earned at the given time.
+    pub fn star(self, day: u8, part: Part, earned_at: &str) -> Self {
+        self.indexed_star(day, part, earned_at, 0)
+    }
+
+    /// Add a star with the index that orders stars earned within the same second.
+    pub fn indexed_star(mut self, day: u8, part: Part, earned_at: &str, star_index: u64) -> Self {
+        let earned_at = ts(earned_at);
+
+        self.0
+            .completion_day_level
+            .entry(DayNumber::new(day).unwrap())
+            .or_default()
+            .parts
+            .insert(
+                part,
+                Challenge {
+                    get_star_ts: earned_at,
+                    star_index,
+                },
+            );
+        self.0.stars += 1;
+        self.0.last_star_ts = self.0.last_star_ts.max(Some(earned_at));
+        self
+    }
+
+    pub fn build(self) -> User {
+        self.0
+    }
+}
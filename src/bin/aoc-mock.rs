@@ -0,0 +1,253 @@
+//! A minimal stand-in for the AoC website, serving private leaderboard statistics from a fixture
+//! file or a generated leaderboard, as well as placeholder puzzle pages. Members gain stars over
+//! time, so the bot can be developed and demoed outside of the AoC event and without a session
+//! cookie.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use axum::extract::Path;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use chrono::prelude::*;
+use log::{debug, info};
+use simplelog::{ColorChoice, ConfigBuilder, LevelFilter, TermLogger, TerminalMode};
+
+use aoc_bot::aoc::{Challenge, DayNumber, LeaderboardStats, Part, User};
+use aoc_bot::scoring;
+
+/// Names for the members of a generated leaderboard.
+const MEMBER_NAMES: &[&str] = &[
+    "Ada Lovelace",
+    "Alan Turing",
+    "Barbara Liskov",
+    "Dennis Ritchie",
+    "Grace Hopper",
+    "Ken Thompson",
+    "Margaret Hamilton",
+];
+
+struct Mock {
+    /// Session cookie that requests must contain. If unset, all requests are accepted.
+    session: Option<String>,
+    stats: Mutex<LeaderboardStats>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+
+    TermLogger::init(
+        LevelFilter::Debug,
+        ConfigBuilder::new()
+            .add_filter_allow_str("aoc_mock")
+            .build(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    )?;
+
+    let address = env::var("AOC_MOCK_ADDRESS")
+        .unwrap_or_else(|_| "127.0.0.1:8000".to_owned())
+        .parse::<SocketAddr>()
+        .context("Failed to parse mock address")?;
+    let star_interval = env::var("AOC_MOCK_STAR_INTERVAL")
+        .map(|interval| humantime::parse_duration(&interval))
+        .unwrap_or(Ok(Duration::from_secs(60)))
+        .context("Failed to parse mock star interval")?;
+
+    let stats = match env::var("AOC_MOCK_FIXTURE") {
+        Ok(path) => {
+            let content = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("failed loading fixture at '{}'", path))?;
+            serde_json::from_slice(&content)
+                .with_context(|| format!("failed parsing fixture from '{}'", path))?
+        }
+        Err(_) => generate_leaderboard(),
+    };
+
+    let mock = Arc::new(Mock {
+        session: env::var("AOC_MOCK_SESSION").ok(),
+        stats: Mutex::new(stats),
+    });
+
+    tokio::spawn(simulate_progress(Arc::clone(&mock), star_interval));
+
+    let app = Router::new()
+        .route(
+            "/:year/leaderboard/private/view/:file",
+            get(private_leaderboard),
+        )
+        .route("/:year/day/:day", get(puzzle))
+        .layer(Extension(mock));
+
+    info!("Listening on http://{}", address);
+
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await
+        .context("failed running server")
+}
+
+/// Serve the leaderboard statistics, if the file name matches the leaderboard's owner ID, like
+/// the real AoC API does.
+async fn private_leaderboard(
+    Extension(mock): Extension<Arc<Mock>>,
+    Path((year, file)): Path<(u16, String)>,
+    headers: HeaderMap,
+) -> Response {
+    debug!("Leaderboard {} of {} requested", file, year);
+
+    if let Some(session) = &mock.session {
+        let expected = format!("session={}", session);
+        let valid = headers
+            .get(header::COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.split("; ").any(|c| c == expected));
+
+        if !valid {
+            return Redirect::to(&format!("/{}/leaderboard/private", year)).into_response();
+        }
+    }
+
+    let stats = mock.stats.lock().unwrap();
+    let board_id = file.strip_suffix(".json").unwrap_or_default();
+
+    if board_id != stats.owner_id {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    Json(LeaderboardStats {
+        event: year.to_string(),
+        ..stats.clone()
+    })
+    .into_response()
+}
+
+/// Serve a placeholder puzzle page, that has the same heading structure as the real ones.
+async fn puzzle(Path((year, day)): Path<(u16, String)>) -> Response {
+    debug!("Puzzle of day {} in {} requested", day, year);
+
+    match day.parse::<DayNumber>() {
+        Ok(day) => Html(format!(
+            "<!DOCTYPE html>\n<html><body><main><article class=\"day-desc\">\
+            <h2>--- Day {0}: Mock Puzzle {0} ---</h2><p>Solve all the things!</p>\
+            </article></main></body></html>",
+            day
+        ))
+        .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Create a leaderboard where nobody solved any challenge yet.
+fn generate_leaderboard() -> LeaderboardStats {
+    let members = MEMBER_NAMES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let id = (100_001 + i).to_string();
+            let user = User {
+                id: id.clone(),
+                name: Some((*name).to_owned()),
+                stars: 0,
+                local_score: 0,
+                global_score: 0,
+                last_star_ts: None,
+                completion_day_level: BTreeMap::new(),
+            };
+            (id, user)
+        })
+        .collect();
+
+    LeaderboardStats {
+        event: Utc::now().year().to_string(),
+        owner_id: "100001".to_owned(),
+        members,
+    }
+}
+
+/// Let a member solve their next challenge in every interval. Members are picked in a fixed but
+/// uneven order, so some of them progress faster than others.
+async fn simulate_progress(mock: Arc<Mock>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    let mut seed = 17_usize;
+
+    loop {
+        interval.tick().await;
+
+        let mut stats = mock.stats.lock().unwrap();
+        let mut ids = stats.members.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+
+        if ids.is_empty() {
+            continue;
+        }
+
+        // Take the lower of two pseudo-random picks, to favor the first members a bit.
+        let mut next_index = || {
+            seed = (seed * 31 + 7) % 1009;
+            seed % ids.len()
+        };
+        let id = &ids[next_index().min(next_index())];
+        let star_index = stats
+            .members
+            .values()
+            .flat_map(|member| member.completion_day_level.values())
+            .flat_map(|level| level.iter().map(|(_, challenge)| challenge.star_index + 1))
+            .max()
+            .unwrap_or_default();
+        let member = match stats.members.get_mut(id) {
+            Some(member) => member,
+            None => continue,
+        };
+
+        if let Some((day, part)) = solve_next(member, star_index) {
+            info!("{} solved day {} part {}", member.display_name(), day, part);
+            update_local_scores(&mut stats);
+        }
+    }
+}
+
+/// Add the next missing star to a member, returning the solved day and part.
+fn solve_next(member: &mut User, star_index: u64) -> Option<(DayNumber, Part)> {
+    let next_index = member.stars as usize;
+    let day = DayNumber::all().nth(next_index / 2)?;
+    let part = Part::ALL[next_index % 2];
+    let now = Utc::now();
+
+    member
+        .completion_day_level
+        .entry(day)
+        .or_default()
+        .parts
+        .insert(
+            part,
+            Challenge {
+                get_star_ts: now,
+                star_index,
+            },
+        );
+    member.stars += 1;
+    member.last_star_ts = Some(now);
+
+    Some((day, part))
+}
+
+/// Re-calculate the local score of all members, the same way the AoC website does.
+fn update_local_scores(stats: &mut LeaderboardStats) {
+    let scores = scoring::points(stats, |_| true)
+        .into_iter()
+        .map(|(id, points)| (id.to_owned(), points))
+        .collect::<HashMap<_, _>>();
+
+    for member in stats.members.values_mut() {
+        member.local_score = scores.get(&member.id).copied().unwrap_or_default();
+    }
+}
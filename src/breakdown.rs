@@ -0,0 +1,75 @@
+//! Breakdown of a single day of a leaderboard, as shown by the `/day` command.
+
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use twilight_model::channel::embed::Embed;
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+use crate::aoc::{self, DayNumber, LeaderboardStats, Part};
+use crate::pagination;
+use crate::scoring;
+
+/// Render the breakdown of a single day, with the order in which members solved each part, how long
+/// it took them after the unlock and the local points they got for that day.
+pub fn embed(stats: &LeaderboardStats, year: u16, day: DayNumber, board_name: &str) -> Embed {
+    let unlock = aoc::unlock_time(year, day);
+    let mut embed = EmbedBuilder::new().title(format!("AoC Day {} {} [{}]", day, year, board_name));
+
+    for part in Part::ALL {
+        let mut solves = stats
+            .members
+            .values()
+            .filter_map(|member| {
+                let challenge = member.completion_day_level.get(&day)?.get(part)?;
+                Some((challenge.get_star_ts, challenge.star_index, member))
+            })
+            .collect::<Vec<_>>();
+        solves.sort_by_key(|&(earned_at, star_index, _)| (earned_at, star_index));
+
+        let lines = solves
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (earned_at, _, member))| {
+                let elapsed = (earned_at - unlock).to_std().unwrap_or_default();
+                format!(
+                    "{}. **{}** after {}",
+                    idx + 1,
+                    member.display_name(),
+                    humantime::format_duration(Duration::from_secs(elapsed.as_secs()))
+                )
+            })
+            .collect::<Vec<_>>();
+
+        embed = embed.field(
+            EmbedFieldBuilder::new(
+                format!("Part {}", part),
+                pagination::field_value(&lines, "Nobody solved this part yet"),
+            )
+            .build(),
+        );
+    }
+
+    let points = scoring::day_points(stats, day);
+    let mut ranked = stats
+        .members
+        .values()
+        .filter_map(|member| Some((points.get(member.id.as_str()).copied()?, member)))
+        .collect::<Vec<_>>();
+    ranked.sort_by_key(|&(points, member)| (Reverse(points), &member.id));
+
+    let lines = ranked
+        .into_iter()
+        .map(|(points, member)| format!("**{}** - {} points", member.display_name(), points))
+        .collect::<Vec<_>>();
+
+    embed
+        .field(
+            EmbedFieldBuilder::new(
+                "Points",
+                pagination::field_value(&lines, "Nobody got any points yet"),
+            )
+            .build(),
+        )
+        .build()
+}
@@ -0,0 +1,770 @@
+//! Registry of all commands the bot understands, together with their arguments and help texts. It
+//! is the single source for parsing legacy `!` commands, validating slash command options and
+//! registering the slash commands with Discord, as well as explaining them in the help.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::num::NonZeroU64;
+use std::str::FromStr;
+
+use once_cell::sync::Lazy;
+use twilight_model::application::command::{Command as SlashCommand, CommandType};
+use twilight_model::application::interaction::application_command::{
+    CommandDataOption, CommandOptionValue,
+};
+use twilight_model::channel::embed::Embed;
+use twilight_model::guild::Permissions;
+use twilight_util::builder::command::{CommandBuilder, IntegerBuilder, StringBuilder, UserBuilder};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+use crate::aoc::{self, DayNumber};
+use crate::export::Format;
+use crate::pagination;
+use crate::render::chart::Metric;
+use crate::scoring::ScoringMode;
+
+/// Prefix of the legacy commands, that are sent as plain messages.
+pub const PREFIX: &str = "!";
+/// Maximum amount of members that can be shown in a chart, to keep it readable.
+const MAX_CHART_MEMBERS: i64 = 20;
+
+/// All commands of the bot.
+pub static COMMANDS: Lazy<Vec<Command>> = Lazy::new(|| {
+    vec![
+        Command::new(
+            "ping",
+            "Check how long the bot needs to interact with Discord",
+        )
+        .examples(&["!ping"]),
+        Command::new("aoc", "Show the leaderboard of this channel")
+            .aliases(&["leaderboard", "lb"])
+            .needs_board()
+            .arg(
+                Arg::new(
+                    "sort",
+                    "How to rank the members",
+                    ArgKind::Choice(ScoringMode::ALL.map(|mode| mode.name().to_owned()).to_vec()),
+                )
+                .flag(&["score"]),
+            )
+            .arg(
+                Arg::new(
+                    "limit",
+                    "Amount of members to show, starting with the best one",
+                    ArgKind::Integer { min: 1, max: None },
+                )
+                .flag(&[]),
+            )
+            .arg(Arg::year())
+            .examples(&["!aoc", "!aoc 2020", "!aoc --sort stars --limit 10 2021"]),
+        Command::new("day", "Show who solved the puzzle of a single day")
+            .needs_board()
+            .arg(
+                Arg::new(
+                    "day",
+                    "Day of the puzzle",
+                    ArgKind::Integer {
+                        min: DayNumber::FIRST.get().into(),
+                        max: Some(DayNumber::LAST.get().into()),
+                    },
+                )
+                .required(),
+            )
+            .arg(Arg::year())
+            .examples(&["!day 5", "!day 5 2020"]),
+        Command::new("grid", "Show the stars of each member as grid")
+            .needs_board()
+            .arg(Arg::year())
+            .examples(&["!grid", "!grid 2020"]),
+        Command::new(
+            "chart",
+            "Show how the members progressed over the course of the event",
+        )
+        .needs_board()
+        .arg(Arg::new(
+            "metric",
+            "What to show",
+            ArgKind::Choice(vec![Metric::Score.to_string(), Metric::Stars.to_string()]),
+        ))
+        .arg(
+            Arg::new(
+                "top",
+                "Amount of members to show, starting with the best one",
+                ArgKind::Integer {
+                    min: 1,
+                    max: Some(MAX_CHART_MEMBERS),
+                },
+            )
+            .flag(&[]),
+        )
+        .arg(Arg::year())
+        .examples(&["!chart", "!chart stars --top 5 2020"]),
+        Command::new("export", "Export the leaderboard as file")
+            .needs_board()
+            .arg(Arg::new(
+                "format",
+                "File format",
+                ArgKind::Choice(vec![Format::Csv.to_string(), Format::Json.to_string()]),
+            ))
+            .arg(Arg::year())
+            .examples(&["!export", "!export json 2020"]),
+        Command::new("top3", "Show the podium of the best 3 members")
+            .aliases(&["podium"])
+            .needs_board()
+            .arg(Arg::year())
+            .examples(&["!top3", "!top3 2020"]),
+        Command::new(
+            "link",
+            "Link your Discord account to your member on the leaderboard",
+        )
+        .needs_board()
+        .arg(Arg::new("member", "Your AoC name or ID", ArgKind::Text).required())
+        .examples(&["!link Ada Lovelace"]),
+        Command::new("unlink", "Remove the link to your AoC member").examples(&["!unlink"]),
+        Command::new(
+            "confirm",
+            "Confirm that a user is the AoC member they linked themselves to",
+        )
+        .permissions(Permissions::MANAGE_ROLES)
+        .arg(Arg::new("user", "User whose link to confirm", ArgKind::User).required())
+        .arg(Arg::new(
+            "member",
+            "AoC name or ID to link the user to instead, taking it over from anyone else",
+            ArgKind::Text,
+        ))
+        .examples(&["!confirm @Ada", "!confirm @Ada Ada Lovelace"]),
+        Command::new("42", "The Answer to the Ultimate Question").examples(&["!42"]),
+        Command::new(
+            "help",
+            "Show the commands of this channel, or the details of a single one",
+        )
+        .arg(Arg::new(
+            "command",
+            "Command to show the details of",
+            ArgKind::Text,
+        ))
+        .examples(&["!help", "!help aoc"]),
+    ]
+});
+
+/// Find a command by its name or one of its aliases.
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name || command.aliases.contains(&name))
+}
+
+/// List the given commands with a short description each.
+pub fn help_embed(commands: &[&Command], slash: bool) -> Embed {
+    let prefix = if slash { "/" } else { PREFIX };
+    let lines = commands
+        .iter()
+        .map(|command| format!("`{}{}` - {}", prefix, command.name, command.description))
+        .collect::<Vec<_>>();
+    let details = if slash {
+        "/help command:<command>"
+    } else {
+        "!help <command>"
+    };
+
+    EmbedBuilder::new()
+        .title("Commands")
+        .description(format!(
+            "{}\n\nUse `{}` to learn more about a command.",
+            lines.join("\n"),
+            details
+        ))
+        .build()
+}
+
+/// Explain a single command with its arguments and some examples.
+pub fn command_help_embed(command: &Command, slash: bool) -> Embed {
+    let (title, usage) = if slash {
+        (format!("/{}", command.name), command.slash_usage())
+    } else {
+        (format!("{}{}", PREFIX, command.name), command.usage())
+    };
+
+    let mut embed = EmbedBuilder::new()
+        .title(title)
+        .description(command.description)
+        .field(EmbedFieldBuilder::new("Usage", format!("`{}`", usage)).build());
+
+    if !command.args.is_empty() {
+        let lines = command
+            .args
+            .iter()
+            .map(|arg| {
+                let name = if arg.flag && !slash {
+                    format!("--{}", arg.name)
+                } else {
+                    arg.name.to_owned()
+                };
+                let choices = match &arg.kind {
+                    ArgKind::Choice(choices) => format!(", one of: {}", choices.join(", ")),
+                    _ => String::new(),
+                };
+                let required = if arg.required { ", required" } else { "" };
+
+                format!("`{}` - {}{}{}", name, arg.description, choices, required)
+            })
+            .collect::<Vec<_>>();
+
+        embed = embed.field(
+            EmbedFieldBuilder::new("Arguments", pagination::field_value(&lines, "")).build(),
+        );
+    }
+
+    if !slash && !command.aliases.is_empty() {
+        let aliases = command
+            .aliases
+            .iter()
+            .map(|alias| format!("`{}{}`", PREFIX, alias))
+            .collect::<Vec<_>>();
+
+        embed = embed.field(EmbedFieldBuilder::new("Aliases", aliases.join(", ")).build());
+    }
+
+    let examples = command
+        .examples
+        .iter()
+        .filter_map(|&example| {
+            if slash {
+                command.slash_example(example)
+            } else {
+                Some(example.to_owned())
+            }
+        })
+        .map(|example| format!("`{}`", example))
+        .collect::<Vec<_>>();
+
+    if !examples.is_empty() {
+        embed = embed.field(EmbedFieldBuilder::new("Examples", examples.join("\n")).build());
+    }
+
+    embed.build()
+}
+
+/// A single command, with everything needed to parse it and explain it to users.
+pub struct Command {
+    pub name: &'static str,
+    /// Alternative names for the legacy command. Slash commands only use the main name.
+    pub aliases: &'static [&'static str],
+    pub description: &'static str,
+    pub args: Vec<Arg>,
+    /// Permissions a member needs in the channel to use the command.
+    pub permissions: Permissions,
+    /// Whether the command only works in channels with a leaderboard.
+    pub needs_board: bool,
+    /// Example invocations, as legacy command.
+    pub examples: &'static [&'static str],
+}
+
+impl Command {
+    fn new(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            aliases: &[],
+            description,
+            args: Vec::new(),
+            permissions: Permissions::empty(),
+            needs_board: false,
+            examples: &[],
+        }
+    }
+
+    fn aliases(mut self, aliases: &'static [&'static str]) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    fn needs_board(mut self) -> Self {
+        self.needs_board = true;
+        self
+    }
+
+    fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    fn examples(mut self, examples: &'static [&'static str]) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// Short description of the legacy command's syntax, like `!day <day> [year]`.
+    pub fn usage(&self) -> String {
+        let mut usage = format!("{}{}", PREFIX, self.name);
+
+        for arg in &self.args {
+            let value = match &arg.kind {
+                ArgKind::Choice(choices) => choices.join("|"),
+                _ => arg.name.to_owned(),
+            };
+            let value = match (arg.flag, arg.required) {
+                (true, _) => format!("[--{} <{}>]", arg.name, value),
+                (false, true) => format!("<{}>", value),
+                (false, false) => format!("[{}]", value),
+            };
+
+            usage.push(' ');
+            usage.push_str(&value);
+        }
+
+        usage
+    }
+
+    /// Short description of the slash command's syntax, like `/day day:<day> [year:<year>]`.
+    pub fn slash_usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+
+        for arg in &self.args {
+            let value = if arg.required {
+                format!("{0}:<{0}>", arg.name)
+            } else {
+                format!("[{0}:<{0}>]", arg.name)
+            };
+
+            usage.push(' ');
+            usage.push_str(&value);
+        }
+
+        usage
+    }
+
+    /// Translate an example of the legacy command into the slash command, like `!day 5` into
+    /// `/day day:5`.
+    pub fn slash_example(&self, example: &str) -> Option<String> {
+        let words = example.split_whitespace().skip(1).collect::<Vec<_>>();
+        let args = self.parse(&words).ok()?;
+        let mut slash = format!("/{}", self.name);
+
+        for arg in &self.args {
+            if let Some(value) = args.0.get(arg.name) {
+                slash.push_str(&format!(" {}:{}", arg.name, value));
+            }
+        }
+
+        Some(slash)
+    }
+
+    /// Parse the words after a legacy command. Flags like `--limit 10` can be anywhere, while the
+    /// remaining words are matched against the positional arguments in order. Optional arguments
+    /// are skipped if a word doesn't fit them, so `!export 2020` is the same as `!export csv 2020`.
+    pub fn parse(&self, words: &[&str]) -> Result<Args, String> {
+        let mut args = Args::default();
+        let mut positional = Vec::new();
+        let mut words = words.iter();
+
+        while let Some(&word) = words.next() {
+            let name = match word.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    positional.push(word);
+                    continue;
+                }
+            };
+
+            let arg = self
+                .args
+                .iter()
+                .find(|arg| arg.flag && (arg.name == name || arg.aliases.contains(&name)))
+                .ok_or_else(|| format!("`{}` is not an option of this command", word))?;
+            let value = words
+                .next()
+                .ok_or_else(|| format!("`{}` needs a value", word))?;
+
+            args.0.insert(arg.name, arg.kind.check(arg.name, value)?);
+        }
+
+        let mut candidates = self.args.iter().filter(|arg| !arg.flag);
+        let mut positional = positional.as_slice();
+
+        while let Some((&word, rest)) = positional.split_first() {
+            let mut skipped = None;
+
+            loop {
+                let arg = candidates.next().ok_or_else(|| {
+                    skipped
+                        .take()
+                        .unwrap_or_else(|| "Too many arguments".to_owned())
+                })?;
+
+                // Text takes all remaining words, as names can contain spaces.
+                if let ArgKind::Text = arg.kind {
+                    args.0.insert(arg.name, positional.join(" "));
+                    positional = &[];
+                    break;
+                }
+
+                match arg.kind.check(arg.name, word) {
+                    Ok(value) => {
+                        args.0.insert(arg.name, value);
+                        positional = rest;
+                        break;
+                    }
+                    Err(e) if !arg.required => {
+                        skipped.get_or_insert(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        self.check_required(&args)?;
+
+        Ok(args)
+    }
+
+    /// Read the options of a slash command. Discord already checks them, but they're validated
+    /// the same way as legacy commands in case the registered command is outdated.
+    pub fn parse_options(&self, options: &[CommandDataOption]) -> Result<Args, String> {
+        let mut args = Args::default();
+
+        for option in options {
+            let arg = match self.args.iter().find(|arg| arg.name == option.name) {
+                Some(arg) => arg,
+                None => continue,
+            };
+            let value = match &option.value {
+                CommandOptionValue::Integer(value) => value.to_string(),
+                CommandOptionValue::String(value) => value.trim().to_owned(),
+                CommandOptionValue::User(id) => id.to_string(),
+                _ => continue,
+            };
+
+            args.0.insert(arg.name, arg.kind.check(arg.name, &value)?);
+        }
+
+        self.check_required(&args)?;
+
+        Ok(args)
+    }
+
+    fn check_required(&self, args: &Args) -> Result<(), String> {
+        match self
+            .args
+            .iter()
+            .find(|arg| arg.required && args.0.get(arg.name).is_none_or(|v| v.is_empty()))
+        {
+            Some(arg) => Err(format!("Missing the `{}` argument", arg.name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Definition of the slash command, to register it with Discord.
+    pub fn slash_command(&self) -> SlashCommand {
+        let mut builder = CommandBuilder::new(
+            self.name.to_owned(),
+            self.description.to_owned(),
+            CommandType::ChatInput,
+        )
+        .dm_permission(false);
+
+        if !self.permissions.is_empty() {
+            builder = builder.default_member_permissions(self.permissions);
+        }
+
+        for arg in &self.args {
+            let name = arg.name.to_owned();
+            let description = arg.description.to_owned();
+
+            builder = match &arg.kind {
+                ArgKind::Integer { min, max } => {
+                    let option = IntegerBuilder::new(name, description)
+                        .min_value(*min)
+                        .required(arg.required);
+                    builder.option(match max {
+                        Some(max) => option.max_value(*max),
+                        None => option,
+                    })
+                }
+                // No upper bound, as the commands are only registered at startup and it would
+                // lag behind once a new event starts. The year is checked on every use instead.
+                ArgKind::Year => builder.option(
+                    IntegerBuilder::new(name, description)
+                        .min_value(aoc::FIRST_EVENT_YEAR.into())
+                        .required(arg.required),
+                ),
+                ArgKind::Choice(choices) => builder.option(
+                    StringBuilder::new(name, description)
+                        .choices(
+                            choices
+                                .iter()
+                                .map(|choice| (choice.clone(), choice.clone())),
+                        )
+                        .required(arg.required),
+                ),
+                ArgKind::Text => {
+                    builder.option(StringBuilder::new(name, description).required(arg.required))
+                }
+                ArgKind::User => {
+                    builder.option(UserBuilder::new(name, description).required(arg.required))
+                }
+            };
+        }
+
+        builder.build()
+    }
+}
+
+/// A single argument of a command.
+pub struct Arg {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+    /// Whether the legacy command takes it as `--name <value>` instead of by position.
+    pub flag: bool,
+    /// Alternative flag names.
+    pub aliases: &'static [&'static str],
+}
+
+impl Arg {
+    fn new(name: &'static str, description: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            description,
+            kind,
+            required: false,
+            flag: false,
+            aliases: &[],
+        }
+    }
+
+    /// The optional event year, that most commands take.
+    fn year() -> Self {
+        Self::new(
+            "year",
+            "Event year, instead of the current one",
+            ArgKind::Year,
+        )
+    }
+
+    fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    fn flag(mut self, aliases: &'static [&'static str]) -> Self {
+        self.flag = true;
+        self.aliases = aliases;
+        self
+    }
+}
+
+/// The type of value an argument takes.
+pub enum ArgKind {
+    /// A whole number, within the range.
+    Integer { min: i64, max: Option<i64> },
+    /// The year of an AoC event.
+    Year,
+    /// One of the listed words.
+    Choice(Vec<String>),
+    /// Any text. In legacy commands, it takes all remaining words.
+    Text,
+    /// A Discord user, which is mentioned in legacy commands.
+    User,
+}
+
+impl ArgKind {
+    /// Make sure the value is valid for this kind, or explain to the user why it isn't. Returns
+    /// the value in the form that is passed on to the command.
+    fn check(&self, name: &str, value: &str) -> Result<String, String> {
+        match self {
+            Self::Integer { min, max } => match value.parse::<i64>() {
+                Ok(n) if n >= *min && max.is_none_or(|max| n <= max) => Ok(value.to_owned()),
+                _ => Err(match max {
+                    Some(max) => format!(
+                        "`{}` is not valid for `{}`, it must be between {} and {}",
+                        value, name, min, max
+                    ),
+                    None => format!(
+                        "`{}` is not valid for `{}`, it must be at least {}",
+                        value, name, min
+                    ),
+                }),
+            },
+            Self::Year => match value.parse() {
+                Ok(year) if aoc::is_event_year(year) => Ok(value.to_owned()),
+                _ => Err(invalid_year(value)),
+            },
+            Self::Choice(choices) if choices.iter().any(|choice| choice == value) => {
+                Ok(value.to_owned())
+            }
+            Self::Choice(choices) => Err(format!(
+                "`{}` is not valid for `{}`, available values are: {}",
+                value,
+                name,
+                choices.join(", ")
+            )),
+            Self::Text => Ok(value.to_owned()),
+            // Mentions look like `<@123>`, or `<@!123>` for nicknames, but plain IDs work too.
+            Self::User => value
+                .strip_prefix("<@")
+                .and_then(|id| id.strip_suffix('>'))
+                .map_or(value, |id| id.trim_start_matches('!'))
+                .parse::<NonZeroU64>()
+                .map(|id| id.to_string())
+                .map_err(|_| {
+                    format!(
+                        "`{}` is not valid for `{}`, it must mention a user",
+                        value, name
+                    )
+                }),
+        }
+    }
+}
+
+/// Explain to the user why a year isn't valid.
+fn invalid_year(year: impl Display) -> String {
+    format!(
+        "`{}` is not a valid year, AoC events were held from {} to {}",
+        year,
+        aoc::FIRST_EVENT_YEAR,
+        aoc::latest_event_year()
+    )
+}
+
+/// The validated arguments of a command, by name.
+#[derive(Debug, Default)]
+pub struct Args(HashMap<&'static str, String>);
+
+impl Args {
+    /// Get an argument as the given type, if it was passed.
+    pub fn get<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.0.get(name).and_then(|value| value.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap().strip_prefix(PREFIX).unwrap();
+
+        find(name).unwrap().parse(&words.collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn flags_can_be_anywhere() {
+        let args = parse("!aoc --sort stars 2020 --limit 10").unwrap();
+
+        assert_eq!(args.get::<String>("sort").as_deref(), Some("stars"));
+        assert_eq!(args.get::<u16>("year"), Some(2020));
+        assert_eq!(args.get::<usize>("limit"), Some(10));
+    }
+
+    #[test]
+    fn flags_can_use_aliases() {
+        let args = parse("!lb --score time-since-unlock").unwrap();
+
+        assert_eq!(
+            args.get::<ScoringMode>("sort"),
+            Some(ScoringMode::TimeSinceUnlock)
+        );
+    }
+
+    #[test]
+    fn unknown_and_incomplete_flags_are_rejected() {
+        assert_eq!(
+            parse("!aoc --top 5").unwrap_err(),
+            "`--top` is not an option of this command"
+        );
+        assert_eq!(
+            parse("!aoc --limit").unwrap_err(),
+            "`--limit` needs a value"
+        );
+        assert!(parse("!aoc --limit 0").is_err());
+    }
+
+    #[test]
+    fn optional_positionals_are_skipped() {
+        let args = parse("!export 2020").unwrap();
+
+        assert_eq!(args.get::<String>("format"), None);
+        assert_eq!(args.get::<u16>("year"), Some(2020));
+
+        let args = parse("!export json 2020").unwrap();
+
+        assert_eq!(args.get::<Format>("format"), Some(Format::Json));
+        assert_eq!(args.get::<u16>("year"), Some(2020));
+    }
+
+    #[test]
+    fn the_first_skipped_error_is_reported() {
+        let err = parse("!export xml").unwrap_err();
+
+        assert!(
+            err.starts_with("`xml` is not valid for `format`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn text_takes_the_remaining_words() {
+        let args = parse("!link Ada  Lovelace").unwrap();
+
+        assert_eq!(
+            args.get::<String>("member").as_deref(),
+            Some("Ada Lovelace")
+        );
+    }
+
+    #[test]
+    fn required_arguments_must_be_given() {
+        assert_eq!(parse("!day").unwrap_err(), "Missing the `day` argument");
+        assert_eq!(parse("!link").unwrap_err(), "Missing the `member` argument");
+    }
+
+    #[test]
+    fn invalid_required_arguments_are_not_skipped() {
+        assert_eq!(
+            parse("!day 26").unwrap_err(),
+            "`26` is not valid for `day`, it must be between 1 and 25"
+        );
+        assert_eq!(
+            parse("!day 2020").unwrap_err(),
+            "`2020` is not valid for `day`, it must be between 1 and 25"
+        );
+    }
+
+    #[test]
+    fn extra_words_are_rejected() {
+        assert_eq!(parse("!day 5 2020 6").unwrap_err(), "Too many arguments");
+        assert_eq!(parse("!ping now").unwrap_err(), "Too many arguments");
+    }
+
+    #[test]
+    fn users_are_parsed_from_mentions_and_ids() {
+        for line in ["!confirm <@42>", "!confirm <@!42>", "!confirm 42"] {
+            let args = parse(line).unwrap();
+
+            assert_eq!(
+                args.get::<NonZeroU64>("user"),
+                NonZeroU64::new(42),
+                "{}",
+                line
+            );
+        }
+
+        assert!(parse("!confirm Ada").is_err());
+        assert!(parse("!confirm <@0>").is_err());
+
+        let args = parse("!confirm <@42> Ada Lovelace").unwrap();
+        assert_eq!(args.get::<NonZeroU64>("user"), NonZeroU64::new(42));
+        assert_eq!(
+            args.get::<String>("member").as_deref(),
+            Some("Ada Lovelace")
+        );
+    }
+}
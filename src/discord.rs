@@ -1,23 +1,87 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use futures_util::stream::StreamExt;
 use log::{debug, error, info};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
 use twilight_cache_inmemory::{InMemoryCache, ResourceType};
 use twilight_gateway::{shard::Events, Event, EventTypeFlags, Shard};
 use twilight_http::Client as HttpClient;
+use twilight_model::application::interaction::application_command::ApplicationCommand;
+use twilight_model::application::interaction::message_component::MessageComponentInteraction;
+use twilight_model::application::interaction::Interaction;
+use twilight_model::guild::{PartialMember, Permissions};
+use twilight_model::id::marker::{ApplicationMarker, ChannelMarker, InteractionMarker};
+use twilight_model::id::Id;
+use twilight_model::util::Timestamp;
 use twilight_model::{channel::Message, gateway::Intents, user::User};
 
+use crate::commands::{self, Args, Command};
+use crate::metrics;
+use crate::models::{ChartArgs, LeaderboardArgs};
+use crate::pagination::PageDirection;
 use crate::settings::Discord;
 
-pub async fn start(settings: &Discord, sender: Sender<crate::models::Event>) -> Result<()> {
-    // Use intents to only receive guild message events.
-    let (shard, events) = Shard::builder(settings.bot_token.clone(), Intents::GUILD_MESSAGES)
-        .event_types(
-            EventTypeFlags::MESSAGE_CREATE
-                | EventTypeFlags::MESSAGE_DELETE
-                | EventTypeFlags::MESSAGE_DELETE_BULK
-                | EventTypeFlags::MESSAGE_UPDATE,
-        )
+/// Start of the Discord epoch in milliseconds since the Unix epoch, which IDs are relative to.
+const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+/// Connect to the Discord gateway and forward all commands to the sender. The returned receiver
+/// tells whether the gateway connection is currently established.
+///
+/// The slash commands are registered for the bot's application before connecting. Commands with a
+/// `!` prefix are only handled if enabled in the settings, as they need the privileged message
+/// content intent.
+pub async fn start(
+    settings: &Discord,
+    http: &HttpClient,
+    sender: Sender<crate::models::Event>,
+) -> Result<watch::Receiver<bool>> {
+    let application_id = http
+        .current_user_application()
+        .exec()
+        .await
+        .context("failed loading the bot's application")?
+        .model()
+        .await?
+        .id;
+
+    let slash_commands = commands::COMMANDS
+        .iter()
+        .map(Command::slash_command)
+        .collect::<Vec<_>>();
+
+    http.interaction(application_id)
+        .set_global_commands(&slash_commands)
+        .exec()
+        .await
+        .context("failed registering slash commands")?;
+
+    debug!("Slash commands registered");
+
+    let mut intents = Intents::empty();
+    let mut event_types = EventTypeFlags::INTERACTION_CREATE
+        | EventTypeFlags::SHARD_CONNECTED
+        | EventTypeFlags::SHARD_DISCONNECTED
+        | EventTypeFlags::SHARD_RECONNECTING
+        | EventTypeFlags::SHARD_RESUMING;
+
+    // Interactions are always sent to the bot, only the legacy commands need any intents. The
+    // guilds are needed to check the permissions of a command's author.
+    if settings.prefix_commands {
+        intents |= Intents::GUILDS | Intents::GUILD_MESSAGES | Intents::MESSAGE_CONTENT;
+        event_types |= EventTypeFlags::MESSAGE_CREATE
+            | EventTypeFlags::GUILD_CREATE
+            | EventTypeFlags::GUILD_UPDATE
+            | EventTypeFlags::GUILD_DELETE
+            | EventTypeFlags::ROLE_CREATE
+            | EventTypeFlags::ROLE_UPDATE
+            | EventTypeFlags::ROLE_DELETE
+            | EventTypeFlags::CHANNEL_CREATE
+            | EventTypeFlags::CHANNEL_UPDATE
+            | EventTypeFlags::CHANNEL_DELETE;
+    }
+
+    let (shard, events) = Shard::builder(settings.bot_token.clone(), intents)
+        .event_types(event_types)
         .build()
         .await?;
 
@@ -34,23 +98,28 @@ pub async fn start(settings: &Discord, sender: Sender<crate::models::Event>) ->
         shard.shutdown();
     });
 
-    // Since we only care about new messages, make the cache only
-    // cache new messages.
+    // Only cache what is needed to calculate the permissions of a legacy command's author, like
+    // for `!confirm`. Slash commands come with the permissions of their author.
     debug!("Setting up cache for twilight");
     let cache = InMemoryCache::builder()
-        .resource_types(ResourceType::MESSAGE)
+        .resource_types(
+            ResourceType::MEMBER | ResourceType::ROLE | ResourceType::GUILD | ResourceType::CHANNEL,
+        )
         .build();
 
+    let (connected_tx, connected_rx) = watch::channel(false);
+
     // Handle Discord events on a separate task.
-    tokio::spawn(handle_events(events, cache, sender));
+    tokio::spawn(handle_events(events, cache, sender, connected_tx));
 
-    Ok(())
+    Ok(connected_rx)
 }
 
 async fn handle_events(
     mut events: Events,
     cache: InMemoryCache,
     sender: Sender<crate::models::Event>,
+    connected: watch::Sender<bool>,
 ) {
     while let Some(event) = events.next().await {
         debug!("Received event : {:?}", event);
@@ -58,19 +127,74 @@ async fn handle_events(
 
         match event {
             Event::MessageCreate(msg) => {
-                let msg = match msg.content.as_str() {
-                    "!ping" => crate::models::Event::Ping(msg.0.into()),
-                    "!aoc" => crate::models::Event::AdventOfCode(msg.0.into()),
-                    "!42" => crate::models::Event::FourtyTwo(msg.0.into()),
-                    "!top3" => crate::models::Event::TopThree(msg.0.into()),
+                let mut words = msg.content.split_whitespace();
+                let command = match words
+                    .next()
+                    .and_then(|word| word.strip_prefix(commands::PREFIX))
+                    .and_then(commands::find)
+                {
+                    Some(command) => command,
+                    None => continue,
+                };
+
+                let allowed = has_permissions(&cache, &msg, command.permissions);
+                let args = command.parse(&words.collect::<Vec<_>>());
+                let msg = crate::models::Message::from(msg.0);
+
+                let msg = match args {
+                    _ if !allowed => crate::models::Event::InvalidCommand(
+                        msg,
+                        missing_permissions(command, commands::PREFIX),
+                    ),
+                    Ok(args) => match command_event(command, msg, &args) {
+                        Some(event) => event,
+                        None => continue,
+                    },
+                    Err(e) => crate::models::Event::InvalidCommand(
+                        msg,
+                        format!("{}\nUsage: `{}`", e, command.usage()),
+                    ),
+                };
+
+                if sender.send(msg).await.is_err() {
+                    return;
+                }
+            }
+            Event::InteractionCreate(interaction) => {
+                let msg = match interaction.0 {
+                    Interaction::ApplicationCommand(command) => parse_application_command(*command),
+                    Interaction::MessageComponent(component) => parse_component(&component),
                     _ => continue,
                 };
 
+                let msg = match msg {
+                    Some(msg) => msg,
+                    None => continue,
+                };
+
                 if sender.send(msg).await.is_err() {
                     return;
                 }
             }
-            Event::ShardConnected(conn) => info!("Connected on shard {}", conn.shard_id),
+            Event::ShardConnected(conn) => {
+                info!("Connected on shard {}", conn.shard_id);
+                connected.send_replace(true);
+            }
+            Event::ShardDisconnected(_) => {
+                connected.send_replace(false);
+            }
+            Event::ShardReconnecting(_) => {
+                metrics::GATEWAY_RECONNECTS
+                    .with_label_values(&["reconnect"])
+                    .inc();
+                connected.send_replace(false);
+            }
+            Event::ShardResuming(_) => {
+                metrics::GATEWAY_RECONNECTS
+                    .with_label_values(&["resume"])
+                    .inc();
+                connected.send_replace(false);
+            }
             _ => {}
         }
     }
@@ -78,6 +202,121 @@ async fn handle_events(
     sender.send(crate::models::Event::Shutdown).await.ok();
 }
 
+/// Whether the author of a legacy command has all permissions in the channel that the command
+/// needs.
+fn has_permissions(cache: &InMemoryCache, msg: &Message, permissions: Permissions) -> bool {
+    permissions.is_empty()
+        || cache
+            .permissions()
+            .in_channel(msg.author.id, msg.channel_id)
+            .is_ok_and(|granted| granted.contains(permissions))
+}
+
+/// Explain which permissions are needed to use a command, like `Manage Roles`.
+fn missing_permissions(command: &Command, prefix: &str) -> String {
+    let names = format!("{:?}", command.permissions)
+        .split(" | ")
+        .map(|name| {
+            name.split('_')
+                .map(|word| word[..1].to_owned() + &word[1..].to_lowercase())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("`, `");
+
+    format!(
+        "You need the `{}` permission to use `{}{}`",
+        names, prefix, command.name
+    )
+}
+
+/// Turn an application command into an event, if it is one of the bot's commands.
+fn parse_application_command(command: ApplicationCommand) -> Option<crate::models::Event> {
+    let spec = commands::find(&command.data.name)?;
+    let msg = crate::models::Message::from(&command);
+
+    // Server admins can allow anybody to use a command, so check the permissions as well.
+    let allowed = spec.permissions.is_empty()
+        || command
+            .member
+            .as_ref()
+            .and_then(|member| member.permissions)
+            .is_some_and(|granted| granted.contains(spec.permissions));
+
+    if !allowed {
+        return Some(crate::models::Event::InvalidCommand(
+            msg,
+            missing_permissions(spec, "/"),
+        ));
+    }
+
+    match spec.parse_options(&command.data.options) {
+        Ok(args) => command_event(spec, msg, &args),
+        Err(e) => Some(crate::models::Event::InvalidCommand(msg, e)),
+    }
+}
+
+/// Turn a pressed button into an event, if it is one of the page buttons.
+fn parse_component(component: &MessageComponentInteraction) -> Option<crate::models::Event> {
+    let direction = PageDirection::from_custom_id(&component.data.custom_id)?;
+
+    Some(crate::models::Event::TurnPage(
+        crate::models::Message::from(component),
+        component.message.id.into(),
+        direction,
+    ))
+}
+
+/// Create the event for a command from its parsed arguments.
+fn command_event(
+    command: &Command,
+    msg: crate::models::Message,
+    args: &Args,
+) -> Option<crate::models::Event> {
+    Some(match command.name {
+        "ping" => crate::models::Event::Ping(msg),
+        "aoc" => crate::models::Event::AdventOfCode(
+            msg,
+            LeaderboardArgs {
+                year: args.get("year"),
+                scoring: args.get("sort"),
+                limit: args.get("limit"),
+            },
+        ),
+        "day" => match args.get("day") {
+            Some(day) => crate::models::Event::Day(msg, day, args.get("year")),
+            None => crate::models::Event::InvalidCommand(msg, "Missing the day".to_owned()),
+        },
+        "grid" => crate::models::Event::Grid(msg, args.get("year")),
+        "chart" => crate::models::Event::Chart(
+            msg,
+            ChartArgs {
+                metric: args.get("metric"),
+                top: args.get("top"),
+                year: args.get("year"),
+            },
+        ),
+        "export" => crate::models::Event::Export(msg, args.get("format"), args.get("year")),
+        "link" => crate::models::Event::Link(msg, args.get("member").unwrap_or_default()),
+        "unlink" => crate::models::Event::Unlink(msg),
+        "confirm" => match args.get("user") {
+            Some(user_id) => crate::models::Event::Confirm(msg, user_id, args.get("member")),
+            None => crate::models::Event::InvalidCommand(msg, "Missing the user".to_owned()),
+        },
+        "42" => crate::models::Event::FourtyTwo(msg),
+        "help" => crate::models::Event::Help(msg, args.get("command")),
+        "top3" => crate::models::Event::TopThree(msg, args.get("year")),
+        _ => return None,
+    })
+}
+
+/// Get the creation time of an interaction, which is encoded in its ID.
+fn interaction_timestamp(id: Id<InteractionMarker>) -> Option<Timestamp> {
+    let millis = (id.get() >> 22) + DISCORD_EPOCH;
+    Timestamp::from_micros(i64::try_from(millis).ok()? * 1000).ok()
+}
+
 pub fn new_client(token: String) -> HttpClient {
     HttpClient::new(token)
 }
@@ -88,10 +327,63 @@ impl From<Message> for crate::models::Message {
             channel_id: m.channel_id.into(),
             author: Some(m.author.into()),
             timestamp: Some(m.timestamp),
+            interaction: None,
         }
     }
 }
 
+impl From<&ApplicationCommand> for crate::models::Message {
+    fn from(c: &ApplicationCommand) -> Self {
+        interaction_message(
+            c.application_id,
+            c.channel_id,
+            c.id,
+            &c.token,
+            c.member.as_ref(),
+            c.user.as_ref(),
+        )
+    }
+}
+
+impl From<&MessageComponentInteraction> for crate::models::Message {
+    fn from(c: &MessageComponentInteraction) -> Self {
+        interaction_message(
+            c.application_id,
+            c.channel_id,
+            c.id,
+            &c.token,
+            c.member.as_ref(),
+            c.user.as_ref(),
+        )
+    }
+}
+
+/// Create the message for any kind of interaction from its common details.
+fn interaction_message(
+    application_id: Id<ApplicationMarker>,
+    channel_id: Id<ChannelMarker>,
+    id: Id<InteractionMarker>,
+    token: &str,
+    member: Option<&PartialMember>,
+    user: Option<&User>,
+) -> crate::models::Message {
+    // The member is set in guilds, the user only in direct messages.
+    let author = member
+        .and_then(|member| member.user.clone())
+        .or_else(|| user.cloned());
+
+    crate::models::Message {
+        channel_id: channel_id.into(),
+        author: author.map(Into::into),
+        timestamp: interaction_timestamp(id),
+        interaction: Some(crate::models::Interaction {
+            application_id: application_id.into(),
+            id: id.into(),
+            token: token.to_owned(),
+        }),
+    }
+}
+
 impl From<User> for crate::models::Author {
     fn from(u: User) -> Self {
         Self {
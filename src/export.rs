@@ -0,0 +1,189 @@
+//! Export of leaderboard statistics as flat table, for further processing in other tools like
+//! spreadsheets.
+
+use std::cmp::Reverse;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::aoc::{DayNumber, LeaderboardStats, Part, User};
+
+/// File formats that the statistics can be exported to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Csv,
+    /// A list of objects, one for each row.
+    Json,
+}
+
+impl Format {
+    /// File extension for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "`{}` is not an export format, available formats are: csv, json",
+                s
+            )),
+        }
+    }
+}
+
+/// Export the statistics with one row per member, ordered by local score. Each row contains the
+/// basic statistics and the time of each star, in RFC 3339 format.
+pub fn export(stats: &LeaderboardStats, format: Format) -> Result<Vec<u8>> {
+    let columns = columns();
+    let mut members = stats.members.values().collect::<Vec<_>>();
+    members.sort_by_key(|member| (Reverse(member.local_score), &member.id));
+
+    let rows = members.into_iter().map(row);
+
+    Ok(match format {
+        Format::Csv => {
+            let mut csv = csv_line(columns.iter().map(|column| Value::from(column.as_str())));
+            for row in rows {
+                csv.push_str(&csv_line(row.into_iter()));
+            }
+            csv.into_bytes()
+        }
+        Format::Json => {
+            let rows = rows
+                .map(|row| columns.iter().cloned().zip(row).collect::<Map<_, _>>())
+                .collect::<Vec<_>>();
+            serde_json::to_vec_pretty(&rows)?
+        }
+    })
+}
+
+/// Names of all columns, with zero-padded day numbers so they sort in order.
+fn columns() -> Vec<String> {
+    let mut columns = [
+        "id",
+        "name",
+        "stars",
+        "local_score",
+        "global_score",
+        "last_star_ts",
+    ]
+    .map(ToOwned::to_owned)
+    .to_vec();
+
+    for day in DayNumber::all() {
+        for part in Part::ALL {
+            columns.push(format!("day{:02}_part{}", day.get(), part));
+        }
+    }
+
+    columns
+}
+
+/// Values of a single member, in the same order as the [`columns`].
+fn row(member: &User) -> Vec<Value> {
+    let mut row = vec![
+        member.id.clone().into(),
+        member.name.clone().map_or(Value::Null, Value::from),
+        member.stars.into(),
+        member.local_score.into(),
+        member.global_score.into(),
+        member
+            .last_star_ts
+            .map_or(Value::Null, |ts| ts.to_rfc3339().into()),
+    ];
+
+    for day in DayNumber::all() {
+        for part in Part::ALL {
+            let challenge = member
+                .completion_day_level
+                .get(&day)
+                .and_then(|level| level.get(part));
+            row.push(challenge.map_or(Value::Null, |c| c.get_star_ts.to_rfc3339().into()));
+        }
+    }
+
+    row
+}
+
+/// Format values as a single line of CSV, quoting them where needed.
+fn csv_line(values: impl Iterator<Item = Value>) -> String {
+    let mut line = values
+        .map(|value| match value {
+            Value::Null => String::new(),
+            Value::String(s) => csv_text(s),
+            value => value.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// Escape a text value for CSV. Spreadsheets run values that start with a formula character as
+/// formula, so names like `=HYPERLINK(...)` get a leading `'` to keep them plain text.
+fn csv_text(mut text: String) -> String {
+    if text.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        text.insert(0, '\'');
+    }
+
+    if text.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_names_are_kept() {
+        assert_eq!(csv_text("Ada Lovelace".to_owned()), "Ada Lovelace");
+        assert_eq!(csv_text("Grace O'Hopper".to_owned()), "Grace O'Hopper");
+    }
+
+    #[test]
+    fn formulas_are_kept_as_text() {
+        assert_eq!(
+            csv_text("=HYPERLINK(\"https://example.com\")".to_owned()),
+            "\"'=HYPERLINK(\"\"https://example.com\"\")\""
+        );
+        assert_eq!(csv_text("-1+2".to_owned()), "'-1+2");
+        assert_eq!(csv_text("+49 123".to_owned()), "'+49 123");
+        assert_eq!(csv_text("@SUM(A1)".to_owned()), "'@SUM(A1)");
+    }
+
+    #[test]
+    fn separators_and_quotes_are_quoted() {
+        assert_eq!(csv_text("Lovelace, Ada".to_owned()), "\"Lovelace, Ada\"");
+        assert_eq!(
+            csv_text("Ada \"The Countess\"".to_owned()),
+            "\"Ada \"\"The Countess\"\"\""
+        );
+        assert_eq!(csv_text("Ada\nLovelace".to_owned()), "\"Ada\nLovelace\"");
+        assert_eq!(
+            csv_text("Ada\r\nLovelace".to_owned()),
+            "\"Ada\r\nLovelace\""
+        );
+    }
+}
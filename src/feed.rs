@@ -0,0 +1,178 @@
+//! Live feed of a leaderboard, that posts who joined, left or earned a star to a Discord channel
+//! shortly after it happened.
+
+use std::num::NonZeroU64;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::Utc;
+use log::{debug, error, info};
+use tokio::time::{self, MissedTickBehavior};
+use twilight_http::Client as DiscordClient;
+
+use crate::aoc::diff::{self, Change};
+use crate::aoc::{self, Client as AocClient, DayNumber, Part};
+use crate::leaderboard::refresh_aoc_data;
+use crate::metrics;
+use crate::settings::AdventOfCode;
+use crate::storage::Store;
+
+/// Maximum length of a Discord message's content.
+const MESSAGE_LIMIT: usize = 2000;
+
+/// Periodically fetch the leaderboards that have a feed channel and post any changes since the
+/// previous fetch to it. The leaderboards are fetched as often as the AoC rate limit allows, but
+/// only while the configured event is running, from the first unlock until the end of December.
+pub async fn run(
+    aoc_client: AocClient,
+    store: Store,
+    discord_client: Arc<DiscordClient>,
+    aoc_settings: Arc<AdventOfCode>,
+) {
+    let start = aoc::unlock_time(aoc_settings.event_year, DayNumber::FIRST);
+    let end = aoc::event_end(aoc_settings.event_year);
+
+    if let Ok(wait) = (start - Utc::now()).to_std() {
+        info!("live feed starts with the event at {}", start);
+        time::sleep(wait).await;
+    }
+
+    let mut interval = time::interval(aoc_client.request_interval());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        if Utc::now() >= end {
+            info!(
+                "event {} is over, stopping the live feed",
+                aoc_settings.event_year
+            );
+            return;
+        }
+
+        metrics::SCHEDULER_TICKS.with_label_values(&["feed"]).inc();
+        metrics::SCHEDULER_TICKS.with_label_values(&["feed"]).inc();
+
+        for board in &aoc_settings.boards {
+            let channel_id = match board.feed_channel_id {
+                Some(channel_id) => channel_id,
+                None => continue,
+            };
+
+            let res = post_updates(
+                &aoc_client,
+                &store,
+                &discord_client,
+                aoc_settings.event_year,
+                &board.id,
+                channel_id,
+            )
+            .await;
+
+            if let Err(e) = res {
+                metrics::record_discord_error(&e);
+                error!("failed updating feed of board {}: {:?}", board.id, e);
+            }
+        }
+    }
+}
+
+/// Fetch a single leaderboard and post the changes compared to the previous snapshot.
+async fn post_updates(
+    aoc_client: &AocClient,
+    store: &Store,
+    discord_client: &DiscordClient,
+    event_year: u16,
+    board_id: &str,
+    channel_id: NonZeroU64,
+) -> Result<()> {
+    // Other tasks save snapshots as well, so the feed keeps track of what it announced on its own.
+    // Before the first announcement, it continues from the latest snapshot.
+    let previous = match store.announced_snapshot(event_year, board_id).await? {
+        Some(previous) => Some(previous),
+        None => store.latest_snapshot(event_year, board_id).await?,
+    };
+    let fetched = refresh_aoc_data(aoc_client, store, event_year, board_id).await?;
+
+    // Without a previous snapshot, everything would be new. Only start announcing from the
+    // current statistics onwards.
+    let previous = match previous {
+        Some(previous) if previous.fetched_at < fetched.fetched_at => previous,
+        Some(_) => return Ok(()),
+        None => {
+            return store
+                .save_announced(event_year, board_id, fetched.fetched_at, &fetched.data)
+                .await;
+        }
+    };
+
+    let lines = diff::diff(&previous.stats, &fetched.data)
+        .into_iter()
+        .map(|change| match change {
+            Change::Joined(member) => {
+                format!(
+                    ":wave: **{}** joined the leaderboard",
+                    member.display_name()
+                )
+            }
+            Change::Left(member) => {
+                format!(":door: **{}** left the leaderboard", member.display_name())
+            }
+            Change::Star {
+                member, day, part, ..
+            } => format!(
+                "{} **{}** solved day {} part {}",
+                if part == Part::One {
+                    ":star:"
+                } else {
+                    ":star2:"
+                },
+                member.display_name(),
+                day,
+                part
+            ),
+        })
+        .collect::<Vec<_>>();
+
+    debug!(
+        "Posting {} feed updates for board {}",
+        lines.len(),
+        board_id
+    );
+
+    for content in chunk_lines(&lines, MESSAGE_LIMIT) {
+        discord_client
+            .create_message(channel_id.into())
+            .content(&content)?
+            .exec()
+            .await?;
+    }
+
+    // Only move on once everything was posted, so failed posts are retried with the next fetch.
+    store
+        .save_announced(event_year, board_id, fetched.fetched_at, &fetched.data)
+        .await
+}
+
+/// Join lines into as few messages as possible, without exceeding the length limit of a message.
+fn chunk_lines(lines: &[String], limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for line in lines {
+        if !current.is_empty() && current.len() + line.len() + 1 > limit {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
@@ -0,0 +1,107 @@
+//! Access to leaderboard statistics, that combines the AoC client with the persistent storage so
+//! the AoC website isn't contacted more often than needed.
+
+use std::ops::Deref;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::error;
+
+use crate::aoc::{Client as AocClient, Fetched, LeaderboardStats};
+use crate::metrics;
+use crate::storage::Store;
+
+/// Maximum age of a stored leaderboard snapshot before fresh statistics are fetched.
+const CACHE_DURATION: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Leaderboard statistics together with the information, whether they came from the storage
+/// instead of being freshly fetched.
+pub struct Cached<T> {
+    pub value: T,
+    pub was_cached: bool,
+}
+
+impl<T> Deref for Cached<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+/// Get the statistics of a private leaderboard. The latest stored snapshot is used if it is recent
+/// enough, otherwise the statistics are fetched from the AoC API and saved to the storage.
+pub async fn get_aoc_data(
+    client: &AocClient,
+    store: &Store,
+    event: u16,
+    leaderboard_id: &str,
+) -> Result<Cached<LeaderboardStats>> {
+    if let Some(snapshot) = store.latest_snapshot(event, leaderboard_id).await? {
+        if Utc::now() - snapshot.fetched_at < chrono::Duration::from_std(CACHE_DURATION)? {
+            metrics::CACHE_LOOKUPS.with_label_values(&["hit"]).inc();
+            return Ok(Cached {
+                value: snapshot.stats,
+                was_cached: true,
+            });
+        }
+    }
+
+    let fetched = refresh_aoc_data(client, store, event, leaderboard_id).await?;
+    metrics::CACHE_LOOKUPS
+        .with_label_values(&[if fetched.fresh { "miss" } else { "hit" }])
+        .inc();
+
+    Ok(Cached {
+        value: fetched.data,
+        was_cached: !fetched.fresh,
+    })
+}
+
+/// Fetch the statistics of a private leaderboard from the AoC API, bypassing the stored snapshots.
+/// Freshly fetched statistics are saved to the storage.
+pub async fn refresh_aoc_data(
+    client: &AocClient,
+    store: &Store,
+    event: u16,
+    leaderboard_id: &str,
+) -> Result<Fetched<LeaderboardStats>> {
+    let fetched = client
+        .get_private_leaderboard_stats(event, leaderboard_id)
+        .await?;
+
+    if fetched.fresh {
+        store
+            .save_snapshot(event, leaderboard_id, fetched.fetched_at, &fetched.data)
+            .await
+            .context("failed saving leaderboard snapshot")?;
+    }
+
+    Ok(fetched)
+}
+
+/// Restore the last request times of the AoC endpoints from the storage, so the rate limit applies
+/// right after a restart as well.
+pub async fn restore_request_times(client: &AocClient, store: &Store) -> Result<()> {
+    for (endpoint, requested_at) in store.request_times().await? {
+        client.record_request(&endpoint, requested_at);
+    }
+
+    Ok(())
+}
+
+/// Save the last request times of the AoC endpoints whenever they change. This includes failed
+/// requests, that don't result in a snapshot, so a bot that restarts over and over again still
+/// doesn't ask the AoC website more often than allowed.
+pub async fn persist_request_times(client: AocClient, store: Store) {
+    let mut times = client.request_times();
+
+    while times.changed().await.is_ok() {
+        let current = times.borrow().clone();
+
+        if let Err(e) = store.save_request_times(current).await {
+            error!("failed saving AoC request times: {:?}", e);
+        }
+    }
+}
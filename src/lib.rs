@@ -1,6 +1,20 @@
 #![deny(rust_2018_idioms, clippy::all)]
 
+pub mod alerts;
 pub mod aoc;
+pub mod breakdown;
+pub mod commands;
 pub mod discord;
+pub mod export;
+pub mod feed;
+pub mod leaderboard;
+pub mod metrics;
 pub mod models;
+pub mod pagination;
+pub mod render;
+pub mod roles;
+pub mod scheduler;
+pub mod scoring;
+pub mod server;
 pub mod settings;
+pub mod storage;
@@ -1,28 +1,57 @@
-use std::num::NonZeroU64;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{fs::File, str::FromStr};
 
 use anyhow::{Context, Result};
-use cached::proc_macro::cached;
-use chrono::Local;
+use chrono::Utc;
 use chrono_humanize::Humanize;
 use cron::Schedule;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use simplelog::{
     ColorChoice, CombinedLogger, ConfigBuilder, SharedLogger, TermLogger, TerminalMode, WriteLogger,
 };
-use tokio::sync::mpsc::{self, Sender};
+use tokio::sync::mpsc;
 use tokio::time;
-use twilight_http::Client as DiscordClient;
+use twilight_http::{error::ErrorType, Client as DiscordClient};
+use twilight_model::application::component::Component;
+use twilight_model::channel::embed::Embed;
+use twilight_model::channel::message::AllowedMentions;
+use twilight_model::channel::Message as DiscordMessage;
+use twilight_model::http::attachment::Attachment;
+use twilight_model::http::interaction::{InteractionResponse, InteractionResponseType};
+use twilight_model::id::{marker::MessageMarker, Id};
 use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+use twilight_util::builder::InteractionResponseDataBuilder;
 
 use aoc_bot::{
-    aoc::{Client as AocClient, LeaderboardStats, User},
-    discord,
+    alerts,
+    aoc::{self, Client as AocClient, Error as AocError, LeaderboardStats, User},
+    breakdown,
+    commands::{self, Command},
+    discord, export, feed,
+    leaderboard::{self, get_aoc_data, Cached},
+    metrics,
     models::{Event, Message},
-    settings::{Logging, Settings},
+    pagination::{self, Paginator},
+    render::{self, chart, grid, podium},
+    roles, scheduler,
+    scoring::{self, Options as ScoringOptions, Ranked},
+    server,
+    settings::{AdventOfCode, Board, Logging, PodiumStyle, Settings},
+    storage::Store,
 };
 
+/// Time to wait after a puzzle unlocked before loading its page, in case the clocks aren't
+/// perfectly in sync.
+const UNLOCK_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// Maximum amount of members shown in the star grid, to limit the image size.
+const MAX_GRID_MEMBERS: usize = 100;
+/// Amount of members shown in a chart, unless requested otherwise.
+const DEFAULT_CHART_MEMBERS: usize = 10;
+/// Time after which the pages of a leaderboard can't be turned anymore. Must be less than the 15
+/// minutes that an interaction's token is valid, to still be able to remove the buttons.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Loading .env file
@@ -30,11 +59,24 @@ async fn main() -> Result<()> {
     // Load settings file
     let settings = Settings::new().await.context("failed loading settings")?;
 
+    // Only check the readiness of an already running bot, for container health checks.
+    if std::env::args().nth(1).as_deref() == Some("healthcheck") {
+        return server::healthcheck(settings.http.as_ref()).await;
+    }
+
     setup_logger(&settings.logging).context("failed setting up logger")?;
 
     info!("Starting ...");
+    let store = Store::open(&settings.storage.path)
+        .await
+        .context("failed opening storage")?;
+
+    // HTTP is separate from the gateway, so create a new client.
+    debug!("Setting up http client for twilight");
+    let discord_client = Arc::new(discord::new_client(settings.discord.bot_token.clone()));
+
     let (events_tx, mut events_rx) = mpsc::channel(1);
-    discord::start(&settings.discord, events_tx.clone())
+    let gateway = discord::start(&settings.discord, &discord_client, events_tx.clone())
         .await
         .context("failed starting Discord listener")?;
 
@@ -44,20 +86,119 @@ async fn main() -> Result<()> {
         let interval =
             Schedule::from_str(&schedule.interval).context("Invalid schedule interval")?;
 
+        let events_tx = events_tx.clone();
+
         tokio::spawn(async move {
-            if let Err(e) = run_scheduler(schedule.channel_id, interval, events_tx).await {
+            if let Err(e) = scheduler::run(schedule.channel_id, interval, events_tx).await {
                 error!("failed running scheduler: {:?}", e);
             }
         });
     }
 
-    // HTTP is separate from the gateway, so create a new client.
-    debug!("Setting up http client for twilight");
+    if let Some(channel_id) = settings.discord.unlock_channel_id {
+        debug!("Setting up puzzle unlock announcements");
+
+        let event_year = settings.aoc.event_year;
+
+        tokio::spawn(async move {
+            if let Err(e) = scheduler::run_unlocks(channel_id, event_year, events_tx).await {
+                error!("failed running unlock scheduler: {:?}", e);
+            }
+        });
+    }
+
+    let aoc_client = AocClient::new(
+        &settings.aoc.session_cookie,
+        &settings.aoc.base_url,
+        settings.aoc.request_interval,
+    )?;
+
+    for board in &settings.aoc.boards {
+        if let Some(snapshot) = store
+            .latest_snapshot(settings.aoc.event_year, &board.id)
+            .await?
+        {
+            aoc_client.prime(
+                snapshot.year,
+                &snapshot.board_id,
+                snapshot.fetched_at,
+                snapshot.stats,
+            );
+        }
+    }
+
+    // Failed requests count towards the rate limit as well, but don't leave a snapshot.
+    leaderboard::restore_request_times(&aoc_client, &store)
+        .await
+        .context("failed restoring AoC request times")?;
+    tokio::spawn(leaderboard::persist_request_times(
+        aoc_client.clone(),
+        store.clone(),
+    ));
+
+    if let Some(alerts) = settings.discord.alerts {
+        debug!("Setting up session health alerts");
+
+        let health = aoc_client.health();
+        let discord_client = Arc::clone(&discord_client);
+
+        tokio::spawn(async move {
+            if let Err(e) = alerts::run(health, discord_client, alerts).await {
+                error!("failed running health alerts: {:?}", e);
+            }
+        });
+    }
+
+    let aoc_settings = Arc::new(settings.aoc);
+    let podium_style = settings.discord.podium;
+    let paginator = Paginator::new();
+
+    if aoc_settings
+        .boards
+        .iter()
+        .any(|board| board.feed_channel_id.is_some())
+    {
+        debug!("Setting up live feed");
+
+        let aoc_client = aoc_client.clone();
+        let store = store.clone();
+        let discord_client = Arc::clone(&discord_client);
+        let aoc_settings = Arc::clone(&aoc_settings);
+
+        tokio::spawn(async move {
+            feed::run(aoc_client, store, discord_client, aoc_settings).await;
+        });
+    }
+
+    if !settings.discord.roles.is_empty() {
+        debug!("Setting up role sync");
+
+        let aoc_client = aoc_client.clone();
+        let store = store.clone();
+        let discord_client = Arc::clone(&discord_client);
+        let aoc_settings = Arc::clone(&aoc_settings);
+        let roles = settings.discord.roles;
+
+        tokio::spawn(async move {
+            roles::run_sync(aoc_client, store, discord_client, aoc_settings, roles).await;
+        });
+    }
+
+    if let Some(http) = settings.http {
+        debug!("Setting up HTTP server");
 
-    let aoc_client = AocClient::new(&settings.aoc.session_cookie)?;
-    let discord_client = Arc::new(discord::new_client(settings.discord.bot_token));
+        let aoc_client = aoc_client.clone();
+        let store = store.clone();
+        let aoc_settings = Arc::clone(&aoc_settings);
 
-    let board_id = Arc::from(settings.aoc.board_id);
+        tokio::spawn(async move {
+            if let Err(e) =
+                server::run(http.address, aoc_client, store, aoc_settings, gateway).await
+            {
+                error!("failed running HTTP server: {:?}", e);
+            }
+        });
+    }
 
     // Process each event as they come in.
     while let Some(event) = events_rx.recv().await {
@@ -65,16 +206,21 @@ async fn main() -> Result<()> {
             break;
         }
 
+        metrics::EVENTS.with_label_values(&[event.name()]).inc();
+
         let fut = handle_event(
             event,
             aoc_client.clone(),
+            store.clone(),
             Arc::clone(&discord_client),
-            Arc::clone(&board_id),
-            settings.aoc.event_year,
+            Arc::clone(&aoc_settings),
+            podium_style,
+            paginator.clone(),
         );
 
         tokio::spawn(async {
             if let Err(e) = fut.await {
+                metrics::record_discord_error(&e);
                 error!("failed handling event: {:?}", e);
             }
         });
@@ -83,55 +229,48 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-#[cached(
-    time = 7200,
-    result = true,
-    with_cached_flag = true,
-    key = "String",
-    convert = r#"{ format!("{}-{}", event, leaderboard_id) }"#
-)]
-async fn get_aoc_data(
-    client: AocClient,
-    event: u16,
-    leaderboard_id: &str,
-) -> Result<cached::Return<LeaderboardStats>> {
-    Ok(cached::Return::new(
-        client
-            .get_private_leaderboard_stats(event, leaderboard_id)
-            .await?,
-    ))
-}
-
 async fn handle_event(
     event: Event,
     aoc_client: AocClient,
+    store: Store,
     discord_client: Arc<DiscordClient>,
-    board_id: Arc<str>,
-    event_year: u16,
+    aoc_settings: Arc<AdventOfCode>,
+    podium_style: PodiumStyle,
+    paginator: Paginator,
 ) -> Result<()> {
+    // Turning a page updates the existing message instead of replying to it.
+    if let Some(msg) = event
+        .message()
+        .filter(|_| !matches!(event, Event::TurnPage(..)))
+    {
+        defer_reply(&discord_client, msg).await?;
+    }
+
     match event {
         Event::Ping(msg) => {
             info!("Ping message");
-            let r = discord_client
-                .create_message(msg.channel_id.into())
-                .content(":ping_pong: Pong! - Latency [000]ms")?
-                .exec()
-                .await?;
-            let resmsg = r.model().await?;
-            discord_client
-                .update_message(msg.channel_id.into(), resmsg.id)
-                .content(Some(
-                    format!(
-                        ":ping_pong: Pong! - Latency [{:0>3}]ms",
-                        (resmsg.timestamp.as_micros() - msg.timestamp.unwrap().as_micros()) / 1000
-                    )
-                    .as_str(),
-                ))?
-                .exec()
-                .await?;
+            let resmsg = reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(":ping_pong: Pong! - Latency [000]ms"),
+                    ..Reply::default()
+                },
+            )
+            .await?;
+            update_reply(
+                &discord_client,
+                &msg,
+                resmsg.id,
+                &format!(
+                    ":ping_pong: Pong! - Latency [{:0>3}]ms",
+                    (resmsg.timestamp.as_micros() - msg.timestamp.unwrap().as_micros()) / 1000
+                ),
+            )
+            .await?;
         }
-        Event::AdventOfCode(msg) => {
-            if let Some(author) = msg.author {
+        Event::AdventOfCode(msg, args) => {
+            if let Some(author) = &msg.author {
                 info!(
                     "Request from ({}) {} to get aoc board",
                     author.id, author.name
@@ -140,92 +279,727 @@ async fn handle_event(
                 info!("Automated request");
             }
 
-            let data = get_aoc_data(aoc_client, event_year, &board_id).await?;
+            let (board, year, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                args.year,
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
+            let scoring = args.scoring.unwrap_or(board.scoring);
 
             debug!(
                 "Retrieved data (cached: {}) -> constructing message",
                 data.was_cached
             );
-            let mut embed = EmbedBuilder::new()
-                .title(format!("AoC Leaderboard [{}]", board_id))
-                .description(format!(
-                    "Here is your current Leaderboard - Cached [{}]",
-                    data.was_cached
-                ));
+            let ranked = scoring::rank(
+                &data,
+                year,
+                scoring,
+                ScoringOptions {
+                    utc_offsets: Some(&board.utc_offsets),
+                    voided_days: &aoc_settings.voided_days,
+                },
+            );
+
+            // Mentions in embeds show the user's name, but never notify them.
+            let links = store.links().await?;
 
-            let mut uvec = data.members.values().collect::<Vec<_>>();
-            uvec.sort_by(|a, b| b.local_score.cmp(&a.local_score));
+            let limit = args.limit.unwrap_or(ranked.len());
+
+            // Ranks are numbered before splitting into pages, so they continue on the next page.
+            let fields = ranked
+                .iter()
+                .enumerate()
+                .take(limit)
+                .map(|(idx, ranked)| {
+                    // Anybody can link themselves to any member, so only show confirmed links.
+                    let linked = links
+                        .get(&ranked.member.id)
+                        .filter(|link| link.confirmed)
+                        .map(|link| format!("\n👤 <@{}>", link.discord_id))
+                        .unwrap_or_default();
 
-            for (idx, user) in uvec.iter().enumerate() {
-                embed = embed.field(
                     EmbedFieldBuilder::new(
                         format!(
-                            "#{} - {} - {} score",
+                            "#{} - {} - {}",
                             idx + 1,
-                            user.name.as_deref().unwrap_or("<anonymous>"),
-                            user.local_score
+                            ranked.member.display_name(),
+                            ranked.score
                         ),
                         format!(
-                            "⭐ Solved {} Challenges\n⏱️ Last at {}",
-                            user.stars,
-                            latest_challenge(user)
+                            "⭐ Solved {} Challenges\n⏱️ Last at {}{}",
+                            ranked.member.stars,
+                            latest_challenge(ranked.member),
+                            linked
                         ),
                     )
                     .inline()
-                    .build(),
-                );
+                    .build()
+                })
+                .collect();
+
+            let pages = pagination::paginate(
+                &format!("AoC Leaderboard {} [{}]", year, board.display_name()),
+                &format!(
+                    "Here is your current Leaderboard - Scoring [{}] - Cached [{}]",
+                    scoring, data.was_cached
+                ),
+                fields,
+            );
+            let buttons = if pages.len() > 1 {
+                pagination::buttons(0, pages.len())
+            } else {
+                Vec::new()
+            };
+
+            debug!(
+                "sending discord message with {} pages to {}",
+                pages.len(),
+                msg.channel_id
+            );
+            let resmsg = reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    embeds: &pages[..1],
+                    components: &buttons,
+                    ..Reply::default()
+                },
+            )
+            .await?;
+
+            if pages.len() > 1 {
+                paginator.insert(resmsg.id.into(), pages);
+
+                // The buttons stop working after the timeout, so remove them instead of leaving
+                // them broken.
+                tokio::spawn(async move {
+                    time::sleep(PAGE_TIMEOUT).await;
+
+                    if paginator.remove(resmsg.id.into()) {
+                        debug!("Removing expired page buttons of {}", resmsg.id);
+
+                        if let Err(e) = remove_components(&discord_client, &msg, resmsg.id).await {
+                            metrics::record_discord_error(&e);
+                            error!("failed removing page buttons of {}: {:?}", resmsg.id, e);
+                        }
+                    }
+                });
             }
-            debug!("sending discord message to {}", msg.channel_id);
-            discord_client
-                .create_message(msg.channel_id.into())
-                .embeds(&[embed.build()])?
-                .exec()
-                .await?;
         }
         Event::FourtyTwo(msg) => {
             info!("42 message");
-            discord_client
-                .create_message(msg.channel_id.into())
-                .content(
-                    ":exploding_head: \
-                    The Answer to the Ultimate Question of Life, \
-                    the Universe, and Everything is 42",
-                )?
-                .exec()
-                .await?;
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(
+                        ":exploding_head: \
+                        The Answer to the Ultimate Question of Life, \
+                        the Universe, and Everything is 42",
+                    ),
+                    ..Reply::default()
+                },
+            )
+            .await?;
         }
-        Event::TopThree(msg) => {
+        Event::TopThree(msg, year) => {
             info!("getting top 3");
 
-            let data = get_aoc_data(aoc_client, event_year, &board_id).await?;
-            let mut uvec = data.members.values().collect::<Vec<_>>();
+            let (board, year, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                year,
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
+            let ranked = scoring::rank(
+                &data,
+                year,
+                board.scoring,
+                ScoringOptions {
+                    utc_offsets: Some(&board.utc_offsets),
+                    voided_days: &aoc_settings.voided_days,
+                },
+            );
+
+            if ranked.len() < 3 {
+                reply(
+                    &discord_client,
+                    &msg,
+                    Reply {
+                        content: Some(
+                            ":exclamation: Sorry, but there are not 3 people on your leaderboard, \
+                            and you do not fill these 3 steps alone",
+                        ),
+                        ..Reply::default()
+                    },
+                )
+                .await?;
+                return Ok(());
+            }
+
+            debug!(
+                "Retrieved data (cached: {}) -> constructing message",
+                data.was_cached
+            );
+
+            // The image font only knows Latin characters, while Discord shows any name in text.
+            let drawable = ranked[..3]
+                .iter()
+                .all(|ranked| render::can_draw(ranked.member.display_name()));
+
+            if podium_style == PodiumStyle::Image && drawable {
+                let image = podium::render(
+                    &ranked,
+                    &format!("AoC Top 3 {} [{}]", year, board.display_name()),
+                )
+                .context("failed rendering podium")?;
+
+                let res = reply(
+                    &discord_client,
+                    &msg,
+                    Reply {
+                        attachments: &[Attachment::from_bytes("podium.png".to_owned(), image, 1)],
+                        ..Reply::default()
+                    },
+                )
+                .await;
 
-            if uvec.len() < 3 {
-                discord_client.create_message(msg.channel_id.into())
-                    .content(":exclamation: Sorry, but there are not 3 people on your leaderboard, and you do not fill these 3 steps alone")?
-                    .exec()
-                    .await?;
+                match res {
+                    Ok(_) => return Ok(()),
+                    Err(e)
+                        if e.downcast_ref::<twilight_http::Error>()
+                            .is_some_and(is_missing_permission) =>
+                    {
+                        metrics::record_discord_error(&e);
+                        warn!("Missing permission to attach files, sending ASCII podium instead");
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&ascii_podium(&ranked)),
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Day(msg, day, year) => {
+            info!("getting day {}", day);
+
+            let year = year.unwrap_or(aoc_settings.event_year);
+            let unlock = aoc::unlock_time(year, day);
+
+            if unlock > Utc::now() {
+                reply(
+                    &discord_client,
+                    &msg,
+                    Reply {
+                        content: Some(&format!(
+                            ":lock: Day {} isn't unlocked yet, come back {}.",
+                            day,
+                            unlock.humanize()
+                        )),
+                        ..Reply::default()
+                    },
+                )
+                .await?;
                 return Ok(());
             }
 
-            uvec.sort_by(|a, b| a.local_score.cmp(&b.local_score).reverse());
+            let (board, _, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                Some(year),
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
 
             debug!(
                 "Retrieved data (cached: {}) -> constructing message",
                 data.was_cached
             );
 
-            let text = format!(
-                "```\n
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    embeds: &[breakdown::embed(&data, year, day, board.display_name())],
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Grid(msg, year) => {
+            info!("rendering star grid");
+
+            let (board, year, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                year,
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
+
+            let members = scoring::rank(
+                &data,
+                year,
+                board.scoring,
+                ScoringOptions {
+                    utc_offsets: Some(&board.utc_offsets),
+                    voided_days: &aoc_settings.voided_days,
+                },
+            )
+            .into_iter()
+            .take(MAX_GRID_MEMBERS)
+            .map(|ranked| ranked.member)
+            .collect::<Vec<_>>();
+
+            let image = grid::render(
+                &members,
+                year,
+                &format!("AoC {} stars [{}]", year, board.display_name()),
+            )
+            .context("failed rendering star grid")?;
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&format!(
+                        ":calendar: Stars of the top {} members in {}",
+                        members.len(),
+                        year
+                    )),
+                    attachments: &[Attachment::from_bytes("grid.png".to_owned(), image, 1)],
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Export(msg, format, year) => {
+            info!("exporting leaderboard");
+
+            let (board, year, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                year,
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
+
+            let format = format.unwrap_or_default();
+            let file = export::export(&data, format).context("failed exporting leaderboard")?;
+            let filename = format!("aoc-{}-{}.{}", year, board.id, format.extension());
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&format!(
+                        ":floppy_disk: Leaderboard {} [{}] with {} members",
+                        year,
+                        board.display_name(),
+                        data.members.len()
+                    )),
+                    attachments: &[Attachment::from_bytes(filename, file, 1)],
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Chart(msg, args) => {
+            info!("rendering chart");
+
+            let (board, year, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                args.year,
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
+
+            let metric = args.metric.unwrap_or_default();
+            let top = args
+                .top
+                .unwrap_or(DEFAULT_CHART_MEMBERS)
+                .min(data.members.len());
+            let image = chart::render(
+                &data,
+                year,
+                metric,
+                top,
+                &format!("AoC {} {} [{}]", year, metric, board.display_name()),
+            )
+            .context("failed rendering chart")?;
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&format!(
+                        ":chart_with_upwards_trend: {} of the top {} members in {}",
+                        match metric {
+                            chart::Metric::Score => "Score",
+                            chart::Metric::Stars => "Stars",
+                        },
+                        top,
+                        year
+                    )),
+                    attachments: &[Attachment::from_bytes("chart.png".to_owned(), image, 1)],
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::PuzzleUnlock(msg, day) => {
+            info!("Announcing puzzle of day {}", day);
+
+            let year = aoc_settings.event_year;
+            time::sleep(UNLOCK_GRACE_PERIOD).await;
+
+            // The announcement is still useful without the title, so don't fail on errors.
+            let title = match aoc_client.get_puzzle_title(year, day).await {
+                Ok(title) => title,
+                Err(e) => {
+                    warn!("failed loading title of day {}: {:?}", day, e);
+                    None
+                }
+            };
+
+            let url = aoc_client.puzzle_url(year, day);
+            let embed = EmbedBuilder::new()
+                .title(match title {
+                    Some(title) => format!("Day {}: {}", day, title),
+                    None => format!("Day {}", day),
+                })
+                .description(format!(
+                    ":christmas_tree: The puzzle of day {} is unlocked, good luck!\n{}",
+                    day, url
+                ))
+                .url(url)
+                .build();
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    embeds: &[embed],
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Link(msg, query) => {
+            let author = match &msg.author {
+                Some(author) => author,
+                None => return Ok(()),
+            };
+
+            info!("Link request from ({}) {}", author.id, author.name);
+
+            let (_, _, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                None,
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
+
+            let content = match find_members(&data, &query).as_slice() {
+                [] => format!(
+                    ":grey_question: There is no member `{}` on the leaderboard. Use your name or \
+                    ID exactly as shown on the AoC website.",
+                    query
+                ),
+                [member] => match store.links().await?.get(&member.id) {
+                    Some(other) if other.discord_id != author.id => format!(
+                        ":x: **{}** is already linked to <@{}>.",
+                        member.display_name(),
+                        other.discord_id
+                    ),
+                    _ => {
+                        store.link(author.id, &member.id).await?;
+                        format!(
+                            ":link: <@{}> is now linked to **{}**. An admin has to `/confirm` \
+                            the link before it counts for any roles.",
+                            author.id,
+                            member.display_name()
+                        )
+                    }
+                },
+                _ => format!(
+                    ":grey_question: There are several members named `{}`, please use your AoC ID \
+                    instead.",
+                    query
+                ),
+            };
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&content),
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Unlink(msg) => {
+            let author = match &msg.author {
+                Some(author) => author,
+                None => return Ok(()),
+            };
+
+            info!("Unlink request from ({}) {}", author.id, author.name);
+
+            let content = match store.unlink(author.id).await? {
+                Some(_) => format!(":broken_chain: <@{}> is not linked anymore.", author.id),
+                None => format!(
+                    ":grey_question: <@{}> isn't linked to any AoC member.",
+                    author.id
+                ),
+            };
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&content),
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Confirm(msg, user_id, Some(query)) => {
+            info!("Linking {} to {} on behalf of an admin", user_id, query);
+
+            let (_, _, data) = match load_board(
+                &aoc_client,
+                &store,
+                &discord_client,
+                &aoc_settings,
+                &msg,
+                None,
+            )
+            .await?
+            {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            };
+
+            let content = match find_members(&data, &query).as_slice() {
+                [] => format!(
+                    ":grey_question: There is no member `{}` on the leaderboard. Use the name or \
+                    ID exactly as shown on the AoC website.",
+                    query
+                ),
+                [member] => match store.assign_link(user_id, &member.id).await? {
+                    Some(previous) => format!(
+                        ":white_check_mark: <@{}> is now linked to **{}** and confirmed, instead \
+                        of <@{}>.",
+                        user_id,
+                        member.display_name(),
+                        previous
+                    ),
+                    None => format!(
+                        ":white_check_mark: <@{}> is now linked to **{}** and confirmed.",
+                        user_id,
+                        member.display_name()
+                    ),
+                },
+                _ => format!(
+                    ":grey_question: There are several members named `{}`, please use the AoC ID \
+                    instead.",
+                    query
+                ),
+            };
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&content),
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Confirm(msg, user_id, None) => {
+            info!("Confirming link of {}", user_id);
+
+            let content = match store.confirm_link(user_id).await? {
+                Some(_) => format!(
+                    ":white_check_mark: The link of <@{}> is confirmed.",
+                    user_id
+                ),
+                None => format!(
+                    ":grey_question: <@{}> isn't linked to any AoC member.",
+                    user_id
+                ),
+            };
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&content),
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::Help(msg, name) => {
+            info!("Help request");
+
+            // Commands that need a leaderboard don't work in channels without one.
+            let has_board = aoc_settings.board_for(msg.channel_id).is_some();
+            let available = |command: &&Command| has_board || !command.needs_board;
+            let slash = msg.interaction.is_some();
+
+            let (content, embeds) = match name {
+                None => {
+                    let available = commands::COMMANDS
+                        .iter()
+                        .filter(available)
+                        .collect::<Vec<_>>();
+                    (None, vec![commands::help_embed(&available, slash)])
+                }
+                Some(name) => {
+                    let command =
+                        commands::find(name.trim_start_matches(['!', '/'])).filter(available);
+                    match command {
+                        Some(command) => (None, vec![commands::command_help_embed(command, slash)]),
+                        None => (
+                            Some(format!(
+                                ":grey_question: `{}` isn't a command that is available in this \
+                                channel.",
+                                name
+                            )),
+                            Vec::new(),
+                        ),
+                    }
+                }
+            };
+
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: content.as_deref(),
+                    embeds: &embeds,
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        Event::TurnPage(msg, message_id, direction) => {
+            let interaction = match &msg.interaction {
+                Some(interaction) => interaction,
+                None => return Ok(()),
+            };
+
+            // Pages that expired already only lose their buttons.
+            let data = match paginator.turn(message_id, direction) {
+                Some((embed, buttons)) => InteractionResponseDataBuilder::new()
+                    .embeds([embed])
+                    .components(buttons),
+                None => InteractionResponseDataBuilder::new().components([]),
+            };
+
+            discord_client
+                .interaction(interaction.application_id.into())
+                .create_response(
+                    interaction.id.into(),
+                    &interaction.token,
+                    &InteractionResponse {
+                        kind: InteractionResponseType::UpdateMessage,
+                        data: Some(data.build()),
+                    },
+                )
+                .exec()
+                .await?;
+        }
+        Event::InvalidCommand(msg, reason) => {
+            info!("Invalid command: {}", reason);
+            reply(
+                &discord_client,
+                &msg,
+                Reply {
+                    content: Some(&format!(":x: {}", reason)),
+                    ..Reply::default()
+                },
+            )
+            .await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Render a podium for the first three of the given members as ASCII art. It's used when images
+/// can't be sent.
+fn ascii_podium(ranked: &[Ranked<'_>]) -> String {
+    format!(
+        "```\n
                 {0:^15}
-                  ↑ {1: ^3} points
+                {1:^15}
                   ★ {2: ^3} stars
                  _____________
                 /     ___     \\
                 |    /   |    |
                 |   /_   |    |
 {3:^15} |     |  |    |    {6:^15}
-↑ {4:^3} points    |     |  |    |    ↑ {7:^3} points
+{4:^15} |     |  |    |    {7:^15}
 ★ {5:^3} stars     |     |__|    |    ★ {8:^3} stars
    _____________|             |_____________
   /    _____                       _____    \\
@@ -235,84 +1009,283 @@ async fn handle_event(
   |   |  |__                       __|  |   |
   |   |_____|                     |_____|   |
   \\_________________________________________/ ```",
-                uvec[0].name.as_deref().unwrap_or("<anonymous>"),
-                uvec[0].local_score,
-                uvec[0].stars,
-                uvec[1].name.as_deref().unwrap_or("<anonymous>"),
-                uvec[1].local_score,
-                uvec[1].stars,
-                uvec[2].name.as_deref().unwrap_or("<anonymous>"),
-                uvec[2].local_score,
-                uvec[2].stars
-            );
+        ranked[0].member.display_name(),
+        ranked[0].score,
+        ranked[0].member.stars,
+        ranked[1].member.display_name(),
+        ranked[1].score,
+        ranked[1].member.stars,
+        ranked[2].member.display_name(),
+        ranked[2].score,
+        ranked[2].member.stars
+    )
+}
 
-            discord_client
+/// Whether a Discord request failed because the bot lacks a permission in the channel.
+fn is_missing_permission(error: &twilight_http::Error) -> bool {
+    matches!(error.kind(), ErrorType::Response { status, .. } if status.get() == 403)
+}
+
+/// Content of a reply to a command.
+#[derive(Default)]
+struct Reply<'a> {
+    content: Option<&'a str>,
+    embeds: &'a [Embed],
+    attachments: &'a [Attachment],
+    components: &'a [Component],
+}
+
+/// Acknowledge an application command right away, as Discord only waits a few seconds for the
+/// first response. It shows a loading state until the actual reply is sent.
+async fn defer_reply(discord_client: &DiscordClient, msg: &Message) -> Result<()> {
+    if let Some(interaction) = &msg.interaction {
+        discord_client
+            .interaction(interaction.application_id.into())
+            .create_response(
+                interaction.id.into(),
+                &interaction.token,
+                &InteractionResponse {
+                    kind: InteractionResponseType::DeferredChannelMessageWithSource,
+                    data: None,
+                },
+            )
+            .exec()
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Reply to a command, either as a follow-up of the application command or as a new message in
+/// the channel for legacy and scheduled commands. Mentions never notify anybody.
+async fn reply(
+    discord_client: &DiscordClient,
+    msg: &Message,
+    reply: Reply<'_>,
+) -> Result<DiscordMessage> {
+    let mentions = AllowedMentions::default();
+
+    let response = match &msg.interaction {
+        Some(interaction) => {
+            let client = discord_client.interaction(interaction.application_id.into());
+            let mut request = client
+                .create_followup(&interaction.token)
+                .allowed_mentions(Some(&mentions));
+
+            if let Some(content) = reply.content {
+                request = request.content(content)?;
+            }
+            if !reply.embeds.is_empty() {
+                request = request.embeds(reply.embeds)?;
+            }
+            if !reply.attachments.is_empty() {
+                request = request.attachments(reply.attachments)?;
+            }
+            if !reply.components.is_empty() {
+                request = request.components(reply.components)?;
+            }
+
+            request.exec().await?
+        }
+        None => {
+            let mut request = discord_client
                 .create_message(msg.channel_id.into())
-                .content(&text)?
+                .allowed_mentions(Some(&mentions));
+
+            if let Some(content) = reply.content {
+                request = request.content(content)?;
+            }
+            if !reply.embeds.is_empty() {
+                request = request.embeds(reply.embeds)?;
+            }
+            if !reply.attachments.is_empty() {
+                request = request.attachments(reply.attachments)?;
+            }
+            if !reply.components.is_empty() {
+                request = request.components(reply.components)?;
+            }
+
+            request.exec().await?
+        }
+    };
+
+    Ok(response.model().await?)
+}
+
+/// Change the content of a previously sent [`reply`].
+async fn update_reply(
+    discord_client: &DiscordClient,
+    msg: &Message,
+    message_id: Id<MessageMarker>,
+    content: &str,
+) -> Result<()> {
+    match &msg.interaction {
+        Some(interaction) => {
+            discord_client
+                .interaction(interaction.application_id.into())
+                .update_followup(&interaction.token, message_id)
+                .content(Some(content))?
+                .exec()
+                .await?;
+        }
+        None => {
+            discord_client
+                .update_message(msg.channel_id.into(), message_id)
+                .content(Some(content))?
                 .exec()
                 .await?;
         }
-        _ => {}
     }
 
     Ok(())
 }
 
-/// Get the latest completion time of the latest challenge from a single user. First check whether
-/// part 1 or 2 was solved latest (as part 2 may not be solved yet) for each day and then compares
-/// this timestamp with the other days.
-fn latest_challenge(user: &User) -> String {
-    let max = user
-        .completion_day_level
-        .values()
-        .map(|day| {
-            if let Some(part2) = &day.part2 {
-                day.part1.get_star_ts.max(part2.get_star_ts)
-            } else {
-                day.part1.get_star_ts
-            }
-        })
-        .max();
+/// Remove all components, like buttons, from a previously sent [`reply`].
+async fn remove_components(
+    discord_client: &DiscordClient,
+    msg: &Message,
+    message_id: Id<MessageMarker>,
+) -> Result<()> {
+    match &msg.interaction {
+        Some(interaction) => {
+            discord_client
+                .interaction(interaction.application_id.into())
+                .update_followup(&interaction.token, message_id)
+                .components(Some(&[]))?
+                .exec()
+                .await?;
+        }
+        None => {
+            discord_client
+                .update_message(msg.channel_id.into(), message_id)
+                .components(Some(&[]))?
+                .exec()
+                .await?;
+        }
+    }
 
-    match max {
-        None => "...never".to_owned(),
-        Some(ts) => ts.humanize(),
+    Ok(())
+}
+
+/// Find the leaderboard of the message's channel and load its statistics for the given year, or
+/// the configured event year. If either fails, the channel is told why and nothing is returned.
+async fn load_board<'a>(
+    aoc_client: &AocClient,
+    store: &Store,
+    discord_client: &DiscordClient,
+    aoc_settings: &'a AdventOfCode,
+    msg: &Message,
+    year: Option<u16>,
+) -> Result<Option<(&'a Board, u16, Cached<LeaderboardStats>)>> {
+    let board = match aoc_settings.board_for(msg.channel_id) {
+        Some(board) => board,
+        None => return reply_no_board(discord_client, msg).await.map(|_| None),
+    };
+
+    let year = year.unwrap_or(aoc_settings.event_year);
+
+    match get_aoc_data(aoc_client, store, year, &board.id).await {
+        Ok(data) => Ok(Some((board, year, data))),
+        Err(e) => reply_aoc_error(discord_client, msg, e).await.map(|_| None),
     }
 }
 
-/// Start up a fixed scheduler that periodically sends leaderboard statistics based on the
-/// configured cron schedule.
-async fn run_scheduler(
-    channel_id: NonZeroU64,
-    interval: Schedule,
-    tx: Sender<Event>,
+/// Let the channel know that there is no leaderboard that could be shown in it.
+async fn reply_no_board(discord_client: &DiscordClient, msg: &Message) -> Result<()> {
+    reply(
+        discord_client,
+        msg,
+        Reply {
+            content: Some(":grey_question: There is no leaderboard configured for this channel."),
+            ..Reply::default()
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Let the channel know why the leaderboard couldn't be loaded, if the error came from the AoC
+/// client. The error is passed through afterwards, so it is still logged.
+async fn reply_aoc_error(
+    discord_client: &DiscordClient,
+    msg: &Message,
+    error: anyhow::Error,
 ) -> Result<()> {
-    let mut interval = interval.upcoming(Local);
+    if let Some(aoc_error) = error.downcast_ref::<AocError>() {
+        let text = match aoc_error {
+            AocError::SessionExpired | AocError::InvalidCookie(_) => {
+                ":lock: The AoC session of this bot expired, so the leaderboard can't be loaded. \
+                Please ask the bot's admin to renew the session cookie."
+                    .to_owned()
+            }
+            AocError::PuzzleNotFound => ":mag: The puzzle isn't available on AoC (yet).".to_owned(),
+            AocError::BoardNotFound => ":mag: The leaderboard couldn't be found on AoC. \
+                Please ask the bot's admin to check the board ID and event year."
+                .to_owned(),
+            AocError::ServerError(status) => format!(
+                ":fire: The AoC website has trouble right now ({}). Please try again later.",
+                status
+            ),
+            AocError::UnexpectedStatus(status) => format!(
+                ":interrobang: The AoC website responded unexpectedly ({}).",
+                status
+            ),
+            AocError::InvalidResponse(_) => ":jigsaw: The AoC website sent the leaderboard in a \
+                format this bot doesn't understand. Maybe the bot needs an update."
+                .to_owned(),
+            AocError::Throttled { retry_in } => format!(
+                ":hourglass: The AoC website may only be asked for this again in {}, \
+                please try again then.",
+                humantime::format_duration(*retry_in)
+            ),
+            AocError::Request(_) => {
+                ":satellite: The AoC website couldn't be reached. Please try again later."
+                    .to_owned()
+            }
+        };
 
-    loop {
-        let next = interval.next().context("no future scheduling event")?;
-        let duration = (next - Local::now()).to_std()?;
+        reply(
+            discord_client,
+            msg,
+            Reply {
+                content: Some(&text),
+                ..Reply::default()
+            },
+        )
+        .await?;
+    }
 
-        debug!(
-            "Next scheduled dashboard message in {}",
-            humantime::format_duration(duration)
-        );
+    Err(error)
+}
 
-        time::sleep(duration).await;
+/// Get the latest completion time of the latest challenge from a single user.
+fn latest_challenge(user: &User) -> String {
+    let max = user.all_star_times().map(|(_, _, ts)| ts).max();
 
-        debug!("Sending new schedule event");
-        let res = tx
-            .send(Event::AdventOfCode(Message {
-                channel_id,
-                author: None,
-                timestamp: None,
-            }))
-            .await;
+    match max {
+        None => "...never".to_owned(),
+        Some(ts) => ts.humanize(),
+    }
+}
 
-        if let Err(e) = res {
-            error!("failed sending scheduled leaderboard: {:?}", e);
-        }
+/// Find the members of a leaderboard that match the given AoC ID, or otherwise the given name,
+/// ignoring case.
+fn find_members<'a>(stats: &'a LeaderboardStats, query: &str) -> Vec<&'a User> {
+    if let Some(member) = stats.members.get(query) {
+        return vec![member];
     }
+
+    stats
+        .members
+        .values()
+        .filter(|member| {
+            member
+                .name
+                .as_deref()
+                .is_some_and(|name| name.eq_ignore_ascii_case(query))
+        })
+        .collect()
 }
 
 /// Set up an combined logger which will log to the terminal and a file. Whether a logger is enabled
@@ -0,0 +1,104 @@
+//! Prometheus metrics about the internals of the bot, that are served by the HTTP server.
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
+    TextEncoder,
+};
+
+/// Handled events, per event type.
+pub static EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aoc_bot_events_total",
+        "Handled events, per event type",
+        &["event"]
+    )
+    .unwrap()
+});
+
+/// Duration of requests to the AoC website, per endpoint.
+pub static AOC_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aoc_bot_aoc_request_duration_seconds",
+        "Duration of requests to the AoC website, per endpoint",
+        &["endpoint"]
+    )
+    .unwrap()
+});
+
+/// Failed requests to the AoC website, per endpoint and error kind.
+pub static AOC_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aoc_bot_aoc_errors_total",
+        "Failed requests to the AoC website, per endpoint and error kind",
+        &["endpoint", "kind"]
+    )
+    .unwrap()
+});
+
+/// Lookups of leaderboard statistics, by whether they were served from the storage (`hit`) or had
+/// to be fetched (`miss`).
+pub static CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aoc_bot_cache_lookups_total",
+        "Lookups of leaderboard statistics, by whether they were served from the storage",
+        &["result"]
+    )
+    .unwrap()
+});
+
+/// Failed requests to the Discord API, per response status.
+pub static DISCORD_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aoc_bot_discord_errors_total",
+        "Failed requests to the Discord API, per response status",
+        &["status"]
+    )
+    .unwrap()
+});
+
+/// Reconnects of the Discord gateway, by whether the session was resumed or started over.
+pub static GATEWAY_RECONNECTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aoc_bot_gateway_reconnects_total",
+        "Reconnects of the Discord gateway, by whether the session was resumed",
+        &["kind"]
+    )
+    .unwrap()
+});
+
+/// Runs of the periodic tasks, per scheduler.
+pub static SCHEDULER_TICKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aoc_bot_scheduler_ticks_total",
+        "Runs of the periodic tasks, per scheduler",
+        &["scheduler"]
+    )
+    .unwrap()
+});
+
+/// Count a failed Discord API request, if the error was caused by one.
+pub fn record_discord_error(error: &anyhow::Error) {
+    if let Some(error) = error.downcast_ref::<twilight_http::Error>() {
+        record_discord_http_error(error);
+    }
+}
+
+/// Count a failed Discord API request.
+pub fn record_discord_http_error(error: &twilight_http::Error) {
+    let status = match error.kind() {
+        twilight_http::error::ErrorType::Response { status, .. } => status.get().to_string(),
+        _ => "none".to_owned(),
+    };
+
+    DISCORD_ERRORS.with_label_values(&[&status]).inc();
+}
+
+/// Encode all metrics in the Prometheus text format.
+pub fn encode() -> Result<String> {
+    let mut buf = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buf)?;
+
+    Ok(String::from_utf8(buf)?)
+}
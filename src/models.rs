@@ -2,20 +2,122 @@ use std::num::NonZeroU64;
 
 use twilight_model::util::Timestamp;
 
+use crate::aoc::DayNumber;
+use crate::export::Format;
+use crate::pagination::PageDirection;
+use crate::render::chart::Metric;
+use crate::scoring::ScoringMode;
+
 #[derive(Debug)]
 pub enum Event {
     Ping(Message),
-    AdventOfCode(Message),
+    /// Show the leaderboard, optionally for another event year or with another scoring mode than
+    /// the configured ones.
+    AdventOfCode(Message, LeaderboardArgs),
     FourtyTwo(Message),
-    TopThree(Message),
+    /// Show the top 3 members, optionally for another event year than the configured one.
+    TopThree(Message, Option<u16>),
+    /// Show who solved the challenges of a single day, optionally for another event year than the
+    /// configured one.
+    Day(Message, DayNumber, Option<u16>),
+    /// Render a grid of the stars each member earned on each day, optionally for another event
+    /// year than the configured one.
+    Grid(Message, Option<u16>),
+    /// Export the leaderboard as file, optionally in another format than CSV or for another event
+    /// year than the configured one.
+    Export(Message, Option<Format>, Option<u16>),
+    /// Render a chart of the members' history.
+    Chart(Message, ChartArgs),
+    /// Link the message's author to the AoC member with the given name or ID.
+    Link(Message, String),
+    /// Remove the link of the message's author to an AoC member.
+    Unlink(Message),
+    /// Confirm the link of the given Discord user to an AoC member. With an AoC name or ID, the
+    /// user is linked to that member first, taking it over from anyone else.
+    Confirm(Message, NonZeroU64, Option<String>),
+    /// List the commands that are available in the channel, or explain the given command.
+    Help(Message, Option<String>),
+    /// A page button below the message with the given ID was pressed.
+    TurnPage(Message, NonZeroU64, PageDirection),
+    /// The puzzle of a day just unlocked and should be announced.
+    PuzzleUnlock(Message, DayNumber),
+    /// A known command was sent with invalid arguments. Contains the reason for the user.
+    InvalidCommand(Message, String),
     Shutdown,
 }
 
+impl Event {
+    /// Short name of the event type, for example to label metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ping(_) => "ping",
+            Self::AdventOfCode(..) => "aoc",
+            Self::FourtyTwo(_) => "42",
+            Self::TopThree(..) => "top3",
+            Self::Day(..) => "day",
+            Self::Grid(..) => "grid",
+            Self::Export(..) => "export",
+            Self::Chart(..) => "chart",
+            Self::Link(..) => "link",
+            Self::Unlink(_) => "unlink",
+            Self::Confirm(..) => "confirm",
+            Self::Help(..) => "help",
+            Self::TurnPage(..) => "turn_page",
+            Self::PuzzleUnlock(..) => "puzzle_unlock",
+            Self::InvalidCommand(..) => "invalid_command",
+            Self::Shutdown => "shutdown",
+        }
+    }
+
+    /// The message that caused the event, if any.
+    pub fn message(&self) -> Option<&Message> {
+        match self {
+            Self::Ping(msg)
+            | Self::AdventOfCode(msg, _)
+            | Self::FourtyTwo(msg)
+            | Self::TopThree(msg, _)
+            | Self::Day(msg, ..)
+            | Self::Grid(msg, _)
+            | Self::Export(msg, ..)
+            | Self::Chart(msg, _)
+            | Self::Link(msg, _)
+            | Self::Unlink(msg)
+            | Self::Confirm(msg, ..)
+            | Self::Help(msg, _)
+            | Self::TurnPage(msg, ..)
+            | Self::PuzzleUnlock(msg, _)
+            | Self::InvalidCommand(msg, _) => Some(msg),
+            Self::Shutdown => None,
+        }
+    }
+}
+
+/// Optional arguments of the leaderboard command.
+#[derive(Debug, Default)]
+pub struct LeaderboardArgs {
+    pub year: Option<u16>,
+    pub scoring: Option<ScoringMode>,
+    /// Amount of members to show, starting from the best one.
+    pub limit: Option<usize>,
+}
+
+/// Optional arguments of the chart command.
+#[derive(Debug, Default)]
+pub struct ChartArgs {
+    pub metric: Option<Metric>,
+    /// Amount of members to show, starting from the best one.
+    pub top: Option<usize>,
+    pub year: Option<u16>,
+}
+
 #[derive(Debug)]
 pub struct Message {
     pub channel_id: NonZeroU64,
     pub author: Option<Author>,
     pub timestamp: Option<Timestamp>,
+    /// Set if the message is an application command or a component interaction, which must be
+    /// replied to with an interaction response instead of a normal message.
+    pub interaction: Option<Interaction>,
 }
 
 #[derive(Debug)]
@@ -23,3 +125,12 @@ pub struct Author {
     pub id: NonZeroU64,
     pub name: String,
 }
+
+/// The details of an application command or component interaction, that are needed to respond to
+/// it.
+#[derive(Debug)]
+pub struct Interaction {
+    pub application_id: NonZeroU64,
+    pub id: NonZeroU64,
+    pub token: String,
+}
@@ -0,0 +1,312 @@
+//! Splitting of embeds that are too large for a single Discord message into pages, which can be
+//! turned with buttons below the message, and trimming of field values that are too long.
+
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::sync::{Arc, Mutex};
+
+use twilight_model::application::component::button::ButtonStyle;
+use twilight_model::application::component::{ActionRow, Button, Component};
+use twilight_model::channel::embed::{Embed, EmbedField};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
+
+/// Prefix of the custom IDs of the page buttons, to tell them apart from other components.
+pub const BUTTON_PREFIX: &str = "page:";
+/// Maximum amount of fields in a single embed.
+const MAX_FIELDS: usize = 25;
+/// Maximum length of the value of a single embed field.
+const FIELD_LIMIT: usize = 1024;
+/// Maximum amount of characters in a single embed, counting all of its texts.
+const EMBED_LIMIT: usize = 6000;
+/// Characters reserved for the page number in the footer.
+const FOOTER_RESERVE: usize = 32;
+
+/// The page to show after a button was pressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageDirection {
+    Previous,
+    Next,
+}
+
+impl PageDirection {
+    /// Get the direction from the custom ID of a page button.
+    pub fn from_custom_id(custom_id: &str) -> Option<Self> {
+        match custom_id.strip_prefix(BUTTON_PREFIX)? {
+            "previous" => Some(Self::Previous),
+            "next" => Some(Self::Next),
+            _ => None,
+        }
+    }
+
+    fn custom_id(self) -> String {
+        let name = match self {
+            Self::Previous => "previous",
+            Self::Next => "next",
+        };
+
+        format!("{}{}", BUTTON_PREFIX, name)
+    }
+}
+
+/// Distribute the fields over as few embeds as possible, keeping their order. Every page gets the
+/// same title and description, and the page number in its footer if there is more than one.
+pub fn paginate(title: &str, description: &str, fields: Vec<EmbedField>) -> Vec<Embed> {
+    let base = title.chars().count() + description.chars().count() + FOOTER_RESERVE;
+    let mut pages = vec![Vec::new()];
+    let mut length = base;
+
+    for field in fields {
+        let field_length = field.name.chars().count() + field.value.chars().count();
+        let current = pages.last_mut().unwrap();
+
+        if !current.is_empty()
+            && (current.len() == MAX_FIELDS || length + field_length > EMBED_LIMIT)
+        {
+            pages.push(vec![field]);
+            length = base + field_length;
+        } else {
+            current.push(field);
+            length += field_length;
+        }
+    }
+
+    let total = pages.len();
+
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(idx, fields)| {
+            let mut embed = EmbedBuilder::new().title(title).description(description);
+
+            for field in fields {
+                embed = embed.field(field);
+            }
+            if total > 1 {
+                embed = embed.footer(EmbedFooterBuilder::new(format!(
+                    "Page {}/{}",
+                    idx + 1,
+                    total
+                )));
+            }
+
+            embed.build()
+        })
+        .collect()
+}
+
+/// Join lines for the value of an embed field, leaving out the last lines if they don't fit.
+pub fn field_value(lines: &[String], empty: &str) -> String {
+    if lines.is_empty() {
+        return empty.to_owned();
+    }
+
+    let mut value = String::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let remaining = lines.len() - idx;
+        // Leave enough room to mention the left out lines.
+        let reserved = if remaining > 1 { 20 } else { 0 };
+
+        if value.len() + line.len() + 1 + reserved > FIELD_LIMIT {
+            value.push_str(&format!("…and {} more", remaining));
+            break;
+        }
+
+        value.push_str(line);
+        value.push('\n');
+    }
+
+    value
+}
+
+/// Buttons to turn the pages, where the ones that would leave the pages are disabled.
+pub fn buttons(current: usize, total: usize) -> Vec<Component> {
+    let button = |direction: PageDirection, label: &str, disabled: bool| {
+        Component::Button(Button {
+            custom_id: Some(direction.custom_id()),
+            disabled,
+            emoji: None,
+            label: Some(label.to_owned()),
+            style: ButtonStyle::Secondary,
+            url: None,
+        })
+    };
+
+    vec![Component::ActionRow(ActionRow {
+        components: vec![
+            button(PageDirection::Previous, "◀ Previous", current == 0),
+            button(PageDirection::Next, "Next ▶", current + 1 >= total),
+        ],
+    })]
+}
+
+/// Pages of the messages that can currently be turned, by the ID of the message.
+#[derive(Clone, Default)]
+pub struct Paginator {
+    messages: Arc<Mutex<HashMap<NonZeroU64, Pages>>>,
+}
+
+struct Pages {
+    embeds: Vec<Embed>,
+    current: usize,
+}
+
+impl Paginator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the pages of a message, that currently shows the first page.
+    pub fn insert(&self, message_id: NonZeroU64, embeds: Vec<Embed>) {
+        self.messages
+            .lock()
+            .unwrap()
+            .insert(message_id, Pages { embeds, current: 0 });
+    }
+
+    /// Turn the page of a message, returning the new page together with its buttons. Nothing is
+    /// returned if the pages of the message aren't known (anymore).
+    pub fn turn(
+        &self,
+        message_id: NonZeroU64,
+        direction: PageDirection,
+    ) -> Option<(Embed, Vec<Component>)> {
+        let mut messages = self.messages.lock().unwrap();
+        let pages = messages.get_mut(&message_id)?;
+
+        pages.current = match direction {
+            PageDirection::Previous => pages.current.saturating_sub(1),
+            PageDirection::Next => (pages.current + 1).min(pages.embeds.len() - 1),
+        };
+
+        Some((
+            pages.embeds[pages.current].clone(),
+            buttons(pages.current, pages.embeds.len()),
+        ))
+    }
+
+    /// Forget the pages of a message, so they can't be turned anymore. Returns whether the pages
+    /// were still known.
+    pub fn remove(&self, message_id: NonZeroU64) -> bool {
+        self.messages.lock().unwrap().remove(&message_id).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use twilight_util::builder::embed::EmbedFieldBuilder;
+
+    use super::*;
+
+    fn fields(count: usize, value: &str) -> Vec<EmbedField> {
+        (1..=count)
+            .map(|idx| EmbedFieldBuilder::new(format!("#{}", idx), value).build())
+            .collect()
+    }
+
+    fn footer(embed: &Embed) -> Option<&str> {
+        embed.footer.as_ref().map(|footer| footer.text.as_str())
+    }
+
+    /// Amount of characters Discord counts for an embed.
+    fn embed_length(embed: &Embed) -> usize {
+        let count = |text: &Option<String>| text.as_deref().map_or(0, |text| text.chars().count());
+
+        count(&embed.title)
+            + count(&embed.description)
+            + embed
+                .footer
+                .as_ref()
+                .map_or(0, |footer| footer.text.chars().count())
+            + embed
+                .fields
+                .iter()
+                .map(|field| field.name.chars().count() + field.value.chars().count())
+                .sum::<usize>()
+    }
+
+    #[test]
+    fn small_boards_fit_on_a_single_page() {
+        let pages = paginate("AoC 2020", "Ranked by local score", fields(10, "42 stars"));
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].fields.len(), 10);
+        assert_eq!(footer(&pages[0]), None);
+    }
+
+    #[test]
+    fn large_boards_are_split_by_the_field_limit() {
+        let pages = paginate("AoC 2020", "Ranked by local score", fields(80, "42 stars"));
+
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| page.fields.len())
+                .collect::<Vec<_>>(),
+            [25, 25, 25, 5]
+        );
+        assert_eq!(
+            pages.iter().map(footer).collect::<Vec<_>>(),
+            [
+                Some("Page 1/4"),
+                Some("Page 2/4"),
+                Some("Page 3/4"),
+                Some("Page 4/4")
+            ]
+        );
+        // Ranks continue on the next page.
+        assert_eq!(pages[1].fields[0].name, "#26");
+        assert!(pages
+            .iter()
+            .all(|page| page.title.as_deref() == Some("AoC 2020")));
+    }
+
+    #[test]
+    fn long_fields_are_split_by_the_embed_limit() {
+        let value = "★".repeat(1000);
+        let pages = paginate("AoC 2020", "Ranked by local score", fields(20, &value));
+
+        assert!(pages.len() > 1);
+        assert!(pages.iter().all(|page| embed_length(page) <= EMBED_LIMIT));
+        assert!(pages.iter().all(|page| page.fields.len() < MAX_FIELDS));
+
+        let names = pages
+            .iter()
+            .flat_map(|page| &page.fields)
+            .map(|field| field.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            (1..=20).map(|idx| format!("#{}", idx)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn empty_field_values_use_the_placeholder() {
+        assert_eq!(field_value(&[], "Nobody yet"), "Nobody yet");
+    }
+
+    #[test]
+    fn long_field_values_are_cut_with_a_note() {
+        let lines = (1..=100)
+            .map(|idx| format!("{}. **Ada Lovelace** ★★", idx))
+            .collect::<Vec<_>>();
+        let value = field_value(&lines, "Nobody yet");
+
+        assert!(value.len() <= FIELD_LIMIT, "{} bytes", value.len());
+
+        let kept = value.lines().count() - 1;
+        assert_eq!(
+            value.lines().last(),
+            Some(&*format!("…and {} more", 100 - kept))
+        );
+        assert!(value.starts_with("1. **Ada Lovelace**"));
+    }
+
+    #[test]
+    fn single_lines_over_the_limit_are_left_out() {
+        let value = field_value(&["x".repeat(2000)], "Nobody yet");
+
+        assert_eq!(value, "…and 1 more");
+    }
+}
@@ -0,0 +1,226 @@
+//! Line charts of the score or stars of members over the course of an event.
+
+use std::cmp::Reverse;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::prelude::*;
+use chrono::Duration;
+
+use super::{Canvas, MUTED, PALETTE, TEXT};
+use crate::aoc::{self, DayNumber, LeaderboardStats, User};
+use crate::scoring;
+
+const WIDTH: u32 = 1000;
+const HEIGHT: u32 = 560;
+/// Space around the plot area, for the title, axis labels and legend.
+const PADDING_TOP: f32 = 56.0;
+const PADDING_LEFT: f32 = 70.0;
+const PADDING_BOTTOM: f32 = 50.0;
+const LEGEND_WIDTH: f32 = 260.0;
+/// Maximum length of member names in the legend.
+const LEGEND_NAME_CHARS: usize = 13;
+
+/// The value that is shown on the chart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// The local score, as calculated by the AoC website.
+    #[default]
+    Score,
+    /// The amount of stars.
+    Stars,
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Score => "score",
+            Self::Stars => "stars",
+        })
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "score" => Ok(Self::Score),
+            "stars" => Ok(Self::Stars),
+            _ => Err(format!(
+                "`{}` is not a chart type, available types are: score, stars",
+                s
+            )),
+        }
+    }
+}
+
+/// The history of a single member's value, as running total after each earned star.
+pub struct Series<'a> {
+    pub member: &'a User,
+    pub values: Vec<(DateTime<Utc>, u32)>,
+}
+
+impl Series<'_> {
+    /// The current value, after the last earned star.
+    pub fn last(&self) -> u32 {
+        self.values.last().map_or(0, |&(_, value)| value)
+    }
+}
+
+/// Reconstruct the history of all members from the times they earned each star, ordered from the
+/// highest to the lowest current value.
+pub fn history(stats: &LeaderboardStats, metric: Metric) -> Vec<Series<'_>> {
+    let mut changes = match metric {
+        Metric::Score => scoring::awarded_points(stats, |_| true),
+        Metric::Stars => stats
+            .members
+            .values()
+            .flat_map(|member| {
+                member
+                    .all_star_times()
+                    .map(move |(_, _, earned_at)| (member, earned_at, 1))
+            })
+            .collect(),
+    };
+    changes.sort_by_key(|&(_, earned_at, _)| earned_at);
+
+    let mut series = stats
+        .members
+        .values()
+        .map(|member| Series {
+            member,
+            values: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    for (member, earned_at, change) in changes {
+        if let Some(series) = series.iter_mut().find(|s| s.member.id == member.id) {
+            let value = series.last() + change;
+            series.values.push((earned_at, value));
+        }
+    }
+
+    series.sort_by_key(|series| (Reverse(series.last()), series.member.id.clone()));
+    series
+}
+
+/// Render a line chart of the given metric for the top members of a leaderboard, as PNG image.
+pub fn render(
+    stats: &LeaderboardStats,
+    year: u16,
+    metric: Metric,
+    top: usize,
+    title: &str,
+) -> Result<Vec<u8>> {
+    let mut series = history(stats, metric);
+    series.truncate(top);
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT)?;
+    canvas.text(20.0, 16.0, title, 2, TEXT);
+
+    let left = PADDING_LEFT;
+    let right = canvas.width() - LEGEND_WIDTH;
+    let top = PADDING_TOP;
+    let bottom = canvas.height() - PADDING_BOTTOM;
+
+    let start = aoc::unlock_time(year, DayNumber::FIRST);
+    let end = series
+        .iter()
+        .filter_map(|series| series.values.last().map(|&(at, _)| at))
+        .max()
+        .unwrap_or(start)
+        .max(start + Duration::days(1));
+    let max = series.iter().map(Series::last).max().unwrap_or(0).max(1);
+
+    let x = |at: DateTime<Utc>| {
+        left + (at - start).num_seconds() as f32 / (end - start).num_seconds() as f32
+            * (right - left)
+    };
+    let y = |value: u32| bottom - value as f32 / max as f32 * (bottom - top);
+
+    // Horizontal grid lines with the values on the y axis.
+    let step = nice_step(max);
+    for value in (0..=max).step_by(step as usize) {
+        let label = value.to_string();
+        canvas.line(&[(left, y(value)), (right, y(value))], 1.0, super::GRID);
+        canvas.text(
+            left - 8.0 - super::text_width(&label, 1),
+            y(value) - 4.0,
+            &label,
+            1,
+            MUTED,
+        );
+    }
+
+    // Day unlocks on the x axis, thinned out if there are too many to fit.
+    let days = DayNumber::all()
+        .map(|day| (day, aoc::unlock_time(year, day)))
+        .filter(|&(_, at)| at <= end)
+        .collect::<Vec<_>>();
+    let every = days.len().div_ceil(13);
+    for (day, at) in days.into_iter().step_by(every.max(1)) {
+        let label = day.to_string();
+        canvas.line(&[(x(at), bottom), (x(at), bottom + 5.0)], 1.0, MUTED);
+        canvas.text(
+            x(at) - super::text_width(&label, 1) / 2.0,
+            bottom + 10.0,
+            &label,
+            1,
+            MUTED,
+        );
+    }
+    canvas.text(
+        (left + right - super::text_width("day", 1)) / 2.0,
+        bottom + 28.0,
+        "day",
+        1,
+        MUTED,
+    );
+
+    canvas.line(&[(left, top), (left, bottom), (right, bottom)], 2.0, MUTED);
+
+    // Draw the best members last, so their lines are on top.
+    for (i, series) in series.iter().enumerate().rev() {
+        let mut points = vec![(x(start), y(0))];
+        let mut previous = 0;
+
+        for &(at, value) in &series.values {
+            points.push((x(at), y(previous)));
+            points.push((x(at), y(value)));
+            previous = value;
+        }
+        points.push((x(end), y(previous)));
+
+        canvas.line(&points, 2.5, PALETTE[i % PALETTE.len()]);
+    }
+
+    for (i, series) in series.iter().enumerate() {
+        let row = top + i as f32 * 24.0;
+        let name = series.member.display_name();
+
+        canvas.fill_rect(right + 20.0, row, 14.0, 14.0, PALETTE[i % PALETTE.len()]);
+        canvas.text(
+            right + 42.0,
+            row - 1.0,
+            &super::truncate(name, LEGEND_NAME_CHARS),
+            2,
+            TEXT,
+        );
+    }
+
+    canvas.encode_png()
+}
+
+/// Find a round step size for the axis labels, so there are about 5 of them.
+fn nice_step(max: u32) -> u32 {
+    let rough = (max / 5).max(1);
+    let magnitude = 10_u32.pow(rough.to_string().len() as u32 - 1);
+
+    [1, 2, 5, 10]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|&step| step >= rough)
+        .unwrap_or(rough)
+}
@@ -0,0 +1,84 @@
+//! A grid of the stars of each member on each day, similar to the private leaderboard page of the
+//! AoC website.
+
+use anyhow::Result;
+use chrono::prelude::*;
+use tiny_skia::ColorU8;
+
+use super::{Canvas, GRID, MUTED, TEXT};
+use crate::aoc::{self, DayNumber, Part, User};
+
+/// Size of a single cell, without the gap to the next one.
+const CELL_SIZE: f32 = 26.0;
+const CELL_GAP: f32 = 6.0;
+const MARGIN: f32 = 20.0;
+/// Space above the grid for the title and day numbers.
+const HEADER_HEIGHT: f32 = 84.0;
+const NAME_WIDTH: f32 = 260.0;
+/// Maximum length of member names, including their rank.
+const NAME_CHARS: usize = 15;
+
+/// Color for days where both parts are solved.
+const GOLD: ColorU8 = ColorU8::from_rgba(0xff, 0xff, 0x66, 255);
+/// Color for days where only the first part is solved.
+const SILVER: ColorU8 = ColorU8::from_rgba(0x99, 0x99, 0xcc, 255);
+
+/// Render the stars of the given members as grid with one row per member, in the given order, and
+/// one column per day.
+pub fn render(members: &[&User], year: u16, title: &str) -> Result<Vec<u8>> {
+    let columns = DayNumber::LAST.get() as f32;
+    let width = MARGIN * 2.0 + NAME_WIDTH + columns * (CELL_SIZE + CELL_GAP);
+    let height = HEADER_HEIGHT + members.len() as f32 * (CELL_SIZE + CELL_GAP) + MARGIN;
+
+    let mut canvas = Canvas::new(width as u32, height as u32)?;
+    canvas.text(MARGIN, 16.0, title, 2, TEXT);
+
+    let now = Utc::now();
+    let left = MARGIN + NAME_WIDTH;
+    let column = |day: DayNumber| left + (day.get() - 1) as f32 * (CELL_SIZE + CELL_GAP);
+
+    for day in DayNumber::all() {
+        let label = day.to_string();
+        canvas.text(
+            column(day) + (CELL_SIZE - super::text_width(&label, 1)) / 2.0,
+            HEADER_HEIGHT - 20.0,
+            &label,
+            1,
+            MUTED,
+        );
+    }
+
+    for (i, member) in members.iter().enumerate() {
+        let top = HEADER_HEIGHT + i as f32 * (CELL_SIZE + CELL_GAP);
+        let name = format!("{}) {}", i + 1, member.display_name());
+
+        canvas.text(
+            MARGIN,
+            top + (CELL_SIZE - 16.0) / 2.0,
+            &super::truncate(&name, NAME_CHARS),
+            2,
+            TEXT,
+        );
+
+        for day in DayNumber::all() {
+            // Leave out days that aren't unlocked yet, to tell them apart from skipped ones.
+            if aoc::unlock_time(year, day) > now {
+                continue;
+            }
+
+            let level = member.completion_day_level.get(&day);
+            let color = match (
+                level.and_then(|level| level.get(Part::One)),
+                level.and_then(|level| level.get(Part::Two)),
+            ) {
+                (Some(_), Some(_)) => GOLD,
+                (Some(_), None) => SILVER,
+                _ => GRID,
+            };
+
+            canvas.fill_rect(column(day), top, CELL_SIZE, CELL_SIZE, color);
+        }
+    }
+
+    canvas.encode_png()
+}
@@ -0,0 +1,171 @@
+//! Rendering of leaderboard statistics into PNG images, that can be attached to Discord messages.
+//! Everything is drawn on the CPU, with a simple bitmap font for text.
+
+use anyhow::{anyhow, Result};
+use font8x8::{UnicodeFonts, BASIC_FONTS, LATIN_FONTS};
+use tiny_skia::{
+    Color, ColorU8, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform,
+};
+
+pub mod chart;
+pub mod grid;
+pub mod podium;
+
+/// Size of a single character of the font in pixels, before scaling.
+const GLYPH_SIZE: u32 = 8;
+
+/// Background color of all images, matching the dark theme of Discord.
+pub const BACKGROUND: ColorU8 = rgb(0x2f, 0x31, 0x36);
+/// Color for regular text.
+pub const TEXT: ColorU8 = rgb(0xdc, 0xdd, 0xde);
+/// Color for less important text and decorations, like axes.
+pub const MUTED: ColorU8 = rgb(0x72, 0x76, 0x7d);
+/// Color for subtle helper lines in the background.
+pub const GRID: ColorU8 = rgb(0x40, 0x44, 0x4b);
+
+/// Colors to tell members apart, for example the lines of a chart. They're re-used from the start,
+/// if there are more members than colors.
+pub const PALETTE: [ColorU8; 10] = [
+    rgb(0xff, 0xd7, 0x00),
+    rgb(0x58, 0x65, 0xf2),
+    rgb(0x57, 0xf2, 0x87),
+    rgb(0xed, 0x42, 0x45),
+    rgb(0xeb, 0x45, 0x9e),
+    rgb(0x00, 0xb0, 0xf4),
+    rgb(0xf4, 0x7b, 0x20),
+    rgb(0x9b, 0x59, 0xb6),
+    rgb(0x1a, 0xbc, 0x9c),
+    rgb(0xbc, 0xc0, 0xc0),
+];
+
+const fn rgb(r: u8, g: u8, b: u8) -> ColorU8 {
+    ColorU8::from_rgba(r, g, b, 255)
+}
+
+/// A drawing surface with a few basic shapes and text.
+pub struct Canvas {
+    pixmap: Pixmap,
+}
+
+impl Canvas {
+    /// Create a new canvas, filled with the background color.
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        let mut pixmap = Pixmap::new(width, height)
+            .ok_or_else(|| anyhow!("invalid image size {}x{}", width, height))?;
+        pixmap.fill(to_color(BACKGROUND));
+
+        Ok(Self { pixmap })
+    }
+
+    pub fn width(&self) -> f32 {
+        self.pixmap.width() as f32
+    }
+
+    pub fn height(&self) -> f32 {
+        self.pixmap.height() as f32
+    }
+
+    /// Fill a rectangle. Rectangles with an empty size are ignored.
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: ColorU8) {
+        if let Some(rect) = Rect::from_xywh(x, y, width, height) {
+            self.pixmap
+                .fill_rect(rect, &paint(color), Transform::identity(), None);
+        }
+    }
+
+    /// Draw a line through all the given points.
+    pub fn line(&mut self, points: &[(f32, f32)], width: f32, color: ColorU8) {
+        let mut builder = PathBuilder::new();
+        let mut points = points.iter();
+
+        if let Some(&(x, y)) = points.next() {
+            builder.move_to(x, y);
+        }
+        for &(x, y) in points {
+            builder.line_to(x, y);
+        }
+
+        if let Some(path) = builder.finish() {
+            let stroke = Stroke {
+                width,
+                line_cap: LineCap::Round,
+                line_join: LineJoin::Round,
+                ..Stroke::default()
+            };
+
+            self.pixmap
+                .stroke_path(&path, &paint(color), &stroke, Transform::identity(), None);
+        }
+    }
+
+    /// Draw text with its top left corner at the given position. Each character is
+    /// `8 * scale` pixels wide and high. Characters that the font doesn't know are shown as `?`.
+    pub fn text(&mut self, x: f32, y: f32, text: &str, scale: u32, color: ColorU8) {
+        let size = scale as f32;
+
+        for (i, c) in text.chars().enumerate() {
+            let glyph = BASIC_FONTS
+                .get(c)
+                .or_else(|| LATIN_FONTS.get(c))
+                .or_else(|| BASIC_FONTS.get('?'))
+                .unwrap_or_default();
+            let left = x + (i as u32 * GLYPH_SIZE * scale) as f32;
+
+            for (row, bits) in glyph.iter().enumerate() {
+                for col in 0..GLYPH_SIZE {
+                    if bits & (1 << col) != 0 {
+                        self.fill_rect(
+                            left + col as f32 * size,
+                            y + row as f32 * size,
+                            size,
+                            size,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Encode the canvas as PNG image.
+    pub fn encode_png(&self) -> Result<Vec<u8>> {
+        self.pixmap.encode_png().map_err(Into::into)
+    }
+}
+
+/// Whether the font has a glyph for every character of the text. It only covers Basic Latin and
+/// Latin-1, so for example CJK characters, Cyrillic or emoji can't be drawn.
+pub fn can_draw(text: &str) -> bool {
+    text.chars()
+        .all(|c| BASIC_FONTS.get(c).is_some() || LATIN_FONTS.get(c).is_some())
+}
+
+/// Width of the given text in pixels, when drawn with [`Canvas::text`].
+pub fn text_width(text: &str, scale: u32) -> f32 {
+    (text.chars().count() as u32 * GLYPH_SIZE * scale) as f32
+}
+
+/// Shorten text to the given amount of characters, marking it with a trailing `~` if it was cut.
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_owned()
+    } else {
+        let mut text = text
+            .chars()
+            .take(max_chars.saturating_sub(1))
+            .collect::<String>();
+        text.push('~');
+        text
+    }
+}
+
+fn to_color(color: ColorU8) -> Color {
+    Color::from_rgba8(color.red(), color.green(), color.blue(), color.alpha())
+}
+
+fn paint(color: ColorU8) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(to_color(color));
+    paint.anti_alias = true;
+    paint
+}
@@ -0,0 +1,70 @@
+//! A podium with the three best members of a leaderboard.
+
+use anyhow::{ensure, Result};
+use tiny_skia::ColorU8;
+
+use super::{Canvas, MUTED, TEXT};
+use crate::scoring::Ranked;
+
+const WIDTH: u32 = 900;
+const HEIGHT: u32 = 520;
+const MARGIN: f32 = 40.0;
+const BLOCK_WIDTH: f32 = 260.0;
+const BLOCK_GAP: f32 = 20.0;
+/// Maximum length of member names above the blocks.
+const NAME_CHARS: usize = 16;
+
+/// Colors and heights of the blocks for the first, second and third place.
+const BLOCKS: [(ColorU8, f32); 3] = [
+    (ColorU8::from_rgba(0xff, 0xd7, 0x00, 255), 260.0),
+    (ColorU8::from_rgba(0xc0, 0xc0, 0xc0, 255), 180.0),
+    (ColorU8::from_rgba(0xcd, 0x7f, 0x32, 255), 130.0),
+];
+
+/// Render a podium for the first three of the given members, that must already be ranked from the
+/// best to the worst.
+pub fn render(members: &[Ranked<'_>], title: &str) -> Result<Vec<u8>> {
+    ensure!(members.len() >= 3, "a podium needs at least 3 members");
+
+    let mut canvas = Canvas::new(WIDTH, HEIGHT)?;
+    canvas.text(20.0, 16.0, title, 2, TEXT);
+
+    let bottom = canvas.height() - MARGIN;
+
+    // The second place is on the left, the first in the middle and the third on the right.
+    for (place, column) in [(1, 0), (0, 1), (2, 2)] {
+        let Ranked { member, score } = members[place];
+        let (color, height) = BLOCKS[place];
+        let left = MARGIN + column as f32 * (BLOCK_WIDTH + BLOCK_GAP);
+        let top = bottom - height;
+        let center =
+            |text: &str, scale| left + (BLOCK_WIDTH - super::text_width(text, scale)) / 2.0;
+
+        canvas.fill_rect(left, top, BLOCK_WIDTH, height, color);
+
+        let rank = (place + 1).to_string();
+        canvas.text(center(&rank, 6), top + 20.0, &rank, 6, super::BACKGROUND);
+
+        let name = super::truncate(member.display_name(), NAME_CHARS);
+        let score = score.to_string();
+        let stars = format!("{} stars", member.stars);
+        // Total times can be too long for the block at the regular size.
+        let score_scale = if score.chars().count() > NAME_CHARS {
+            1
+        } else {
+            2
+        };
+
+        canvas.text(center(&name, 2), top - 76.0, &name, 2, TEXT);
+        canvas.text(
+            center(&score, score_scale),
+            top - 48.0,
+            &score,
+            score_scale,
+            MUTED,
+        );
+        canvas.text(center(&stars, 2), top - 26.0, &stars, 2, MUTED);
+    }
+
+    canvas.encode_png()
+}
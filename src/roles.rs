@@ -0,0 +1,463 @@
+//! Rules for Discord roles that are granted to members depending on their progress in the event,
+//! and the task that keeps the roles of linked members in sync with them.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU64;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{error, info};
+use serde::Deserialize;
+use tokio::time::{self, MissedTickBehavior};
+use twilight_http::{error::ErrorType, Client as DiscordClient};
+
+use crate::aoc::{self, Client as AocClient, DayNumber, LeaderboardStats, Part};
+use crate::leaderboard::refresh_aoc_data;
+use crate::metrics;
+use crate::scoring::{self, Options, ScoringMode};
+use crate::settings::{AdventOfCode, Role};
+use crate::storage::{Grant, Link, Store};
+
+/// The condition a member must fulfill to have a role.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "rule", rename_all = "kebab-case")]
+pub enum RoleRule {
+    /// Both parts of the puzzle that unlocked within the last 24 hours are solved.
+    SolvedToday,
+    /// The amount of stars is at least `min` and, if set, less than `max`. Setting both allows to
+    /// have one role per star bracket.
+    Stars { min: u32, max: Option<u32> },
+    /// The member is among the best `count` members of the leaderboard's scoring mode, and earned
+    /// at least one star.
+    Top { count: usize },
+}
+
+/// Find the IDs of all members that fulfill the rule.
+pub fn eligible<'a>(
+    rule: &RoleRule,
+    stats: &'a LeaderboardStats,
+    year: u16,
+    mode: ScoringMode,
+    options: Options<'_>,
+) -> HashSet<&'a str> {
+    eligible_at(rule, stats, year, mode, options, Utc::now())
+}
+
+/// Find the IDs of all members that fulfill the rule at the given time.
+fn eligible_at<'a>(
+    rule: &RoleRule,
+    stats: &'a LeaderboardStats,
+    year: u16,
+    mode: ScoringMode,
+    options: Options<'_>,
+    now: DateTime<Utc>,
+) -> HashSet<&'a str> {
+    match *rule {
+        RoleRule::SolvedToday => {
+            let today = DayNumber::all().find(|&day| {
+                let unlock = aoc::unlock_time(year, day);
+                unlock <= now && now < unlock + Duration::days(1)
+            });
+
+            stats
+                .members
+                .values()
+                .filter(|member| {
+                    today.is_some_and(|day| {
+                        member
+                            .completion_day_level
+                            .get(&day)
+                            .is_some_and(|level| level.get(Part::Two).is_some())
+                    })
+                })
+                .map(|member| member.id.as_str())
+                .collect()
+        }
+        RoleRule::Stars { min, max } => stats
+            .members
+            .values()
+            .filter(|member| member.stars >= min && max.is_none_or(|max| member.stars < max))
+            .map(|member| member.id.as_str())
+            .collect(),
+        RoleRule::Top { count } => scoring::rank(stats, year, mode, options)
+            .into_iter()
+            .filter(|ranked| ranked.member.stars > 0)
+            .take(count)
+            .map(|ranked| ranked.member.id.as_str())
+            .collect(),
+    }
+}
+
+/// Periodically grant and revoke the configured roles of all linked members, based on the latest
+/// statistics of their leaderboard. Members that aren't linked are never touched, except to revoke
+/// the roles the bot granted them while they were.
+pub async fn run_sync(
+    aoc_client: AocClient,
+    store: Store,
+    discord_client: Arc<DiscordClient>,
+    aoc_settings: Arc<AdventOfCode>,
+    roles: Vec<Role>,
+) {
+    let mut interval = time::interval(aoc_client.request_interval());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        metrics::SCHEDULER_TICKS.with_label_values(&["roles"]).inc();
+
+        let res = sync(&aoc_client, &store, &discord_client, &aoc_settings, &roles).await;
+
+        if let Err(e) = res {
+            metrics::record_discord_error(&e);
+            error!("failed syncing roles: {:?}", e);
+        }
+    }
+}
+
+/// Bring the roles of all linked members in line with the rules. Failures are only logged, so a
+/// single member or role that can't be managed doesn't block all others.
+async fn sync(
+    aoc_client: &AocClient,
+    store: &Store,
+    discord_client: &DiscordClient,
+    aoc_settings: &AdventOfCode,
+    roles: &[Role],
+) -> Result<()> {
+    let year = aoc_settings.event_year;
+    let links = store.links().await?;
+    // Current roles of each guild member, only loaded once per sync.
+    let mut member_roles = HashMap::new();
+
+    for board in &aoc_settings.boards {
+        let rules = roles
+            .iter()
+            .filter(|role| match &role.board {
+                Some(id) => *id == board.id,
+                None => aoc_settings.boards.first().map(|first| &first.id) == Some(&board.id),
+            })
+            .collect::<Vec<_>>();
+
+        if rules.is_empty() {
+            continue;
+        }
+
+        // Always ask for the latest statistics, the client falls back to its previous response if
+        // the request interval didn't pass yet.
+        let data = match refresh_aoc_data(aoc_client, store, year, &board.id).await {
+            Ok(fetched) => fetched.data,
+            Err(e) => {
+                error!("failed loading board {} for roles: {:?}", board.id, e);
+                continue;
+            }
+        };
+
+        for role in rules {
+            let members = eligible(
+                &role.rule,
+                &data,
+                year,
+                board.scoring,
+                Options {
+                    utc_offsets: Some(&board.utc_offsets),
+                    voided_days: &aoc_settings.voided_days,
+                },
+            );
+
+            // Anybody can link themselves to any member, so only trust confirmed links.
+            for (aoc_id, link) in &links {
+                if !link.confirmed || !data.members.contains_key(aoc_id) {
+                    continue;
+                }
+
+                let user_id = link.discord_id;
+
+                let current = match member_roles.entry((role.guild_id, user_id)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let res = discord_client
+                            .guild_member(role.guild_id.into(), user_id.into())
+                            .exec()
+                            .await;
+
+                        let roles = match res {
+                            Ok(member) => match member.model().await {
+                                Ok(member) => Some(
+                                    member
+                                        .roles
+                                        .into_iter()
+                                        .map(NonZeroU64::from)
+                                        .collect::<HashSet<_>>(),
+                                ),
+                                Err(e) => {
+                                    error!("failed reading member {}: {:?}", user_id, e);
+                                    None
+                                }
+                            },
+                            // Not a member of the guild (anymore).
+                            Err(e) if is_not_found(&e) => None,
+                            Err(e) => {
+                                metrics::record_discord_http_error(&e);
+                                error!("failed loading member {}: {:?}", user_id, e);
+                                None
+                            }
+                        };
+
+                        // Members that failed to load are skipped for the rest of the sync.
+                        entry.insert(roles)
+                    }
+                };
+                let current = match current {
+                    Some(current) => current,
+                    None => continue,
+                };
+
+                let should_have = members.contains(aoc_id.as_str());
+                let has = current.contains(&role.role_id);
+                let grant = Grant {
+                    guild_id: role.guild_id,
+                    user_id,
+                    role_id: role.role_id,
+                };
+
+                if should_have && has {
+                    // Also covers roles that were granted before grants were recorded.
+                    store.record_grant(grant).await?;
+                } else if should_have {
+                    info!("Granting role {} to {}", role.role_id, user_id);
+                    let res = discord_client
+                        .add_guild_member_role(
+                            role.guild_id.into(),
+                            user_id.into(),
+                            role.role_id.into(),
+                        )
+                        .exec()
+                        .await;
+
+                    match res {
+                        Ok(_) => {
+                            current.insert(role.role_id);
+                            store.record_grant(grant).await?;
+                        }
+                        Err(e) => {
+                            metrics::record_discord_http_error(&e);
+                            error!(
+                                "failed granting role {} to {}: {:?}",
+                                role.role_id, user_id, e
+                            );
+                        }
+                    }
+                } else if !should_have && has {
+                    info!("Revoking role {} from {}", role.role_id, user_id);
+                    let res = discord_client
+                        .remove_guild_member_role(
+                            role.guild_id.into(),
+                            user_id.into(),
+                            role.role_id.into(),
+                        )
+                        .exec()
+                        .await;
+
+                    match res {
+                        Ok(_) => {
+                            current.remove(&role.role_id);
+                            store.remove_grant(grant).await?;
+                        }
+                        Err(e) => {
+                            metrics::record_discord_http_error(&e);
+                            error!(
+                                "failed revoking role {} from {}: {:?}",
+                                role.role_id, user_id, e
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    revoke_unlinked(store, discord_client, &links).await
+}
+
+/// Revoke the roles the bot granted to users that don't have a confirmed link anymore, because they
+/// unlinked themselves or linked to another member without a new confirmation.
+async fn revoke_unlinked(
+    store: &Store,
+    discord_client: &DiscordClient,
+    links: &HashMap<String, Link>,
+) -> Result<()> {
+    let confirmed = links
+        .values()
+        .filter(|link| link.confirmed)
+        .map(|link| link.discord_id)
+        .collect::<HashSet<_>>();
+
+    for grant in store.grants().await? {
+        if confirmed.contains(&grant.user_id) {
+            continue;
+        }
+
+        info!(
+            "Revoking role {} from unlinked {}",
+            grant.role_id, grant.user_id
+        );
+        let res = discord_client
+            .remove_guild_member_role(
+                grant.guild_id.into(),
+                grant.user_id.into(),
+                grant.role_id.into(),
+            )
+            .exec()
+            .await;
+
+        match res {
+            Ok(_) => store.remove_grant(grant).await?,
+            // The member left the guild or the role was deleted, so there is nothing to revoke.
+            Err(e) if is_not_found(&e) => store.remove_grant(grant).await?,
+            // Kept, to try again with the next sync.
+            Err(e) => {
+                metrics::record_discord_http_error(&e);
+                error!(
+                    "failed revoking role {} from unlinked {}: {:?}",
+                    grant.role_id, grant.user_id, e
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the request failed because the requested resource doesn't exist.
+fn is_not_found(error: &twilight_http::Error) -> bool {
+    matches!(error.kind(), ErrorType::Response { status, .. } if status.get() == 404)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::testing::{member, stats, ts};
+
+    fn eligible_ids(rule: RoleRule, stats: &LeaderboardStats, now: &str) -> Vec<String> {
+        let mut ids = eligible_at(
+            &rule,
+            stats,
+            2020,
+            ScoringMode::Local,
+            Options {
+                utc_offsets: None,
+                voided_days: &[],
+            },
+            ts(now),
+        )
+        .into_iter()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn solved_today_needs_both_parts_of_the_latest_puzzle() {
+        let stats = stats([
+            member("both")
+                .star(5, Part::One, "2020-12-05T05:10:00Z")
+                .star(5, Part::Two, "2020-12-05T05:20:00Z")
+                .build(),
+            member("one-part")
+                .star(5, Part::One, "2020-12-05T05:10:00Z")
+                .build(),
+            member("yesterday")
+                .star(4, Part::One, "2020-12-04T05:10:00Z")
+                .star(4, Part::Two, "2020-12-04T05:20:00Z")
+                .build(),
+        ]);
+
+        // Day 5 unlocked at 05:00 UTC and is the puzzle of the day until day 6 unlocks.
+        assert_eq!(
+            eligible_ids(RoleRule::SolvedToday, &stats, "2020-12-06T04:59:59Z"),
+            ["both"]
+        );
+        assert_eq!(
+            eligible_ids(RoleRule::SolvedToday, &stats, "2020-12-05T04:59:59Z"),
+            ["yesterday"]
+        );
+        // A day after the last unlock, there is no puzzle of the day anymore.
+        assert!(eligible_ids(RoleRule::SolvedToday, &stats, "2020-12-26T05:00:00Z").is_empty());
+        assert!(eligible_ids(RoleRule::SolvedToday, &stats, "2020-11-30T12:00:00Z").is_empty());
+    }
+
+    #[test]
+    fn stars_are_within_the_bracket() {
+        let stats = stats([
+            member("none").build(),
+            member("one")
+                .star(1, Part::One, "2020-12-01T06:00:00Z")
+                .build(),
+            member("two")
+                .star(1, Part::One, "2020-12-01T06:00:00Z")
+                .star(1, Part::Two, "2020-12-01T06:10:00Z")
+                .build(),
+        ]);
+
+        assert_eq!(
+            eligible_ids(
+                RoleRule::Stars { min: 1, max: None },
+                &stats,
+                "2020-12-02T00:00:00Z"
+            ),
+            ["one", "two"]
+        );
+        // The minimum is inclusive, the maximum exclusive.
+        assert_eq!(
+            eligible_ids(
+                RoleRule::Stars {
+                    min: 1,
+                    max: Some(2)
+                },
+                &stats,
+                "2020-12-02T00:00:00Z"
+            ),
+            ["one"]
+        );
+        assert_eq!(
+            eligible_ids(
+                RoleRule::Stars {
+                    min: 0,
+                    max: Some(1)
+                },
+                &stats,
+                "2020-12-02T00:00:00Z"
+            ),
+            ["none"]
+        );
+    }
+
+    #[test]
+    fn top_skips_members_without_stars() {
+        let stats = stats([
+            member("first")
+                .local_score(4)
+                .star(1, Part::One, "2020-12-01T06:00:00Z")
+                .star(1, Part::Two, "2020-12-01T06:10:00Z")
+                .build(),
+            member("second")
+                .local_score(1)
+                .star(1, Part::One, "2020-12-01T07:00:00Z")
+                .build(),
+            member("idle").build(),
+            member("idle-too").build(),
+        ]);
+
+        assert_eq!(
+            eligible_ids(RoleRule::Top { count: 1 }, &stats, "2020-12-02T00:00:00Z"),
+            ["first"]
+        );
+        assert_eq!(
+            eligible_ids(RoleRule::Top { count: 3 }, &stats, "2020-12-02T00:00:00Z"),
+            ["first", "second"]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Schedulers that send events to the bot at fixed times, either periodic leaderboards or the
+//! announcement of each puzzle as it unlocks.
+
+use std::num::NonZeroU64;
+
+use anyhow::{Context, Result};
+use chrono::{Local, Utc};
+use cron::Schedule;
+use log::{debug, error, info};
+use tokio::sync::mpsc::Sender;
+use tokio::time;
+
+use crate::aoc::{self, DayNumber};
+use crate::metrics;
+use crate::models::{Event, LeaderboardArgs, Message};
+
+/// Start up a fixed scheduler that periodically sends leaderboard statistics based on the
+/// configured cron schedule.
+pub async fn run(channel_id: NonZeroU64, interval: Schedule, tx: Sender<Event>) -> Result<()> {
+    let mut interval = interval.upcoming(Local);
+
+    loop {
+        let next = interval.next().context("no future scheduling event")?;
+        let duration = (next - Local::now()).to_std()?;
+
+        debug!(
+            "Next scheduled dashboard message in {}",
+            humantime::format_duration(duration)
+        );
+
+        time::sleep(duration).await;
+        metrics::SCHEDULER_TICKS
+            .with_label_values(&["leaderboard"])
+            .inc();
+
+        debug!("Sending new schedule event");
+        let res = tx
+            .send(Event::AdventOfCode(
+                Message {
+                    channel_id,
+                    author: None,
+                    timestamp: None,
+                    interaction: None,
+                },
+                LeaderboardArgs::default(),
+            ))
+            .await;
+
+        if let Err(e) = res {
+            error!("failed sending scheduled leaderboard: {:?}", e);
+        }
+    }
+}
+
+/// Announce each puzzle of the event as soon as it unlocks. Unlock times are fixed to midnight EST,
+/// independent of the local timezone the bot runs in.
+pub async fn run_unlocks(channel_id: NonZeroU64, event_year: u16, tx: Sender<Event>) -> Result<()> {
+    for day in DayNumber::all() {
+        let unlock = aoc::unlock_time(event_year, day);
+        let duration = match (unlock - Utc::now()).to_std() {
+            Ok(duration) => duration,
+            // Already unlocked.
+            Err(_) => continue,
+        };
+
+        debug!(
+            "Next puzzle unlock (day {}) in {}",
+            day,
+            humantime::format_duration(duration)
+        );
+
+        time::sleep(duration).await;
+        metrics::SCHEDULER_TICKS
+            .with_label_values(&["unlock"])
+            .inc();
+
+        let res = tx
+            .send(Event::PuzzleUnlock(
+                Message {
+                    channel_id,
+                    author: None,
+                    timestamp: None,
+                    interaction: None,
+                },
+                day,
+            ))
+            .await;
+
+        if let Err(e) = res {
+            error!("failed sending puzzle unlock of day {}: {:?}", day, e);
+        }
+    }
+
+    info!("All puzzles of {} are unlocked", event_year);
+
+    Ok(())
+}
@@ -0,0 +1,566 @@
+//! Alternative rankings of a leaderboard, computed locally from the completion timestamps of each
+//! member instead of relying on the local score of the AoC API.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use serde::Deserialize;
+
+use crate::aoc::{self, DayNumber, LeaderboardStats, Part, User};
+
+/// Days that were removed from the scoring by the AoC website, because of server issues during the
+/// unlock.
+const VOIDED_DAYS: &[VoidedDay] = &[VoidedDay {
+    year: 2020,
+    day: DayNumber::FIRST,
+}];
+
+/// The different ways to rank the members of a leaderboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScoringMode {
+    /// The local score as calculated by the AoC website.
+    #[default]
+    Local,
+    /// Only the amount of stars, where earning them earlier breaks ties.
+    Stars,
+    /// The total time it took to solve each challenge, counted from the puzzle unlock.
+    TimeSinceUnlock,
+    /// Like [`Self::TimeSinceUnlock`], but counted from the first midnight in each member's
+    /// timezone after the puzzle unlocked.
+    LocalMidnight,
+    /// The local score, re-calculated without the days that were voided.
+    ExcludeVoided,
+}
+
+impl ScoringMode {
+    /// All available modes.
+    pub const ALL: [Self; 5] = [
+        Self::Local,
+        Self::Stars,
+        Self::TimeSinceUnlock,
+        Self::LocalMidnight,
+        Self::ExcludeVoided,
+    ];
+
+    /// The name of this mode, as used in settings and commands.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Stars => "stars",
+            Self::TimeSinceUnlock => "time-since-unlock",
+            Self::LocalMidnight => "local-midnight",
+            Self::ExcludeVoided => "exclude-voided",
+        }
+    }
+}
+
+impl Display for ScoringMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ScoringMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "`{}` is not a scoring mode, available modes are: {}",
+                    s,
+                    Self::ALL.map(Self::name).join(", ")
+                )
+            })
+    }
+}
+
+/// A day that doesn't count towards the score.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct VoidedDay {
+    pub year: u16,
+    pub day: DayNumber,
+}
+
+/// Additional information for the scoring, that isn't part of the leaderboard statistics.
+#[derive(Clone, Copy, Default)]
+pub struct Options<'a> {
+    /// Timezone of members by their ID, for the [`ScoringMode::LocalMidnight`] mode. Members
+    /// without an entry use the timezone of the event.
+    pub utc_offsets: Option<&'a HashMap<String, FixedOffset>>,
+    /// Voided days in addition to the ones known from the AoC website.
+    pub voided_days: &'a [VoidedDay],
+}
+
+/// The score of a single member, depending on the scoring mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    Points(u32),
+    Stars(u32),
+    /// Total time needed to earn the stars.
+    Time {
+        stars: u32,
+        total: Duration,
+    },
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Points(points) => write!(f, "{} score", points),
+            Self::Stars(stars) => write!(f, "{} stars", stars),
+            Self::Time { stars, total } => write!(
+                f,
+                "{} stars in {}",
+                stars,
+                humantime::format_duration(Duration::from_secs(total.as_secs() / 60 * 60))
+            ),
+        }
+    }
+}
+
+/// A leaderboard member with their score.
+#[derive(Clone, Copy, Debug)]
+pub struct Ranked<'a> {
+    pub member: &'a User,
+    pub score: Score,
+}
+
+/// Rank all members of a leaderboard with the given scoring mode, from the best to the worst.
+pub fn rank<'a>(
+    stats: &'a LeaderboardStats,
+    year: u16,
+    mode: ScoringMode,
+    options: Options<'_>,
+) -> Vec<Ranked<'a>> {
+    let mut ranked = match mode {
+        ScoringMode::Local => stats
+            .members
+            .values()
+            .map(|member| Ranked {
+                member,
+                score: Score::Points(member.local_score),
+            })
+            .collect(),
+        ScoringMode::Stars => stats
+            .members
+            .values()
+            .map(|member| Ranked {
+                member,
+                score: Score::Stars(member.stars),
+            })
+            .collect(),
+        ScoringMode::TimeSinceUnlock => rank_by_time(stats, year, |_| aoc::event_timezone()),
+        ScoringMode::LocalMidnight => rank_by_time(stats, year, |member| {
+            options
+                .utc_offsets
+                .and_then(|offsets| offsets.get(&member.id).copied())
+                .unwrap_or_else(aoc::event_timezone)
+        }),
+        ScoringMode::ExcludeVoided => rank_by_points(stats, |day| {
+            VOIDED_DAYS
+                .iter()
+                .chain(options.voided_days)
+                .all(|voided| voided.year != year || voided.day != day)
+        }),
+    };
+
+    ranked.sort_by_key(|ranked| {
+        let tiebreak = (
+            ranked.member.last_star_ts.is_none(),
+            ranked.member.last_star_ts,
+            ranked.member.id.clone(),
+        );
+
+        match ranked.score {
+            Score::Points(points) => (Reverse(points), Duration::ZERO, tiebreak),
+            Score::Stars(stars) => (Reverse(stars), Duration::ZERO, tiebreak),
+            Score::Time { stars, total } => (Reverse(stars), total, tiebreak),
+        }
+    });
+
+    ranked
+}
+
+/// Calculate the local points that each member got for a single day, by their ID. Members without
+/// any stars on that day aren't included.
+pub fn day_points(stats: &LeaderboardStats, day: DayNumber) -> HashMap<&str, u32> {
+    points(stats, |d| d == day)
+}
+
+/// Re-calculate the local score over the counted days.
+fn rank_by_points(
+    stats: &LeaderboardStats,
+    counted: impl Fn(DayNumber) -> bool,
+) -> Vec<Ranked<'_>> {
+    let points = points(stats, counted);
+
+    stats
+        .members
+        .values()
+        .map(|member| Ranked {
+            member,
+            score: Score::Points(points.get(member.id.as_str()).copied().unwrap_or_default()),
+        })
+        .collect()
+}
+
+/// Calculate the local points of each member over the counted days, keyed by member ID. Over all
+/// days, these are the local scores of the AoC website.
+pub fn points(stats: &LeaderboardStats, counted: impl Fn(DayNumber) -> bool) -> HashMap<&str, u32> {
+    let mut points = HashMap::<&str, u32>::new();
+
+    for (member, _, awarded) in awarded_points(stats, counted) {
+        *points.entry(member.id.as_str()).or_default() += awarded;
+    }
+
+    points
+}
+
+/// Calculate the local points that were awarded for each star of the counted days, together with
+/// the member and the time they earned the star. For each star, the first member to earn it gets as
+/// many points as there are members, the second one a point less and so on.
+pub fn awarded_points(
+    stats: &LeaderboardStats,
+    counted: impl Fn(DayNumber) -> bool,
+) -> Vec<(&User, DateTime<Utc>, u32)> {
+    let count = stats.members.len() as u32;
+    let mut awarded = Vec::new();
+
+    for day in DayNumber::all().filter(|&day| counted(day)) {
+        for part in Part::ALL {
+            let mut solves = stats
+                .members
+                .values()
+                .filter_map(|member| {
+                    let challenge = member.completion_day_level.get(&day)?.get(part)?;
+                    Some((challenge.get_star_ts, challenge.star_index, member))
+                })
+                .collect::<Vec<_>>();
+            solves.sort_by_key(|&(earned_at, star_index, member)| {
+                (earned_at, star_index, &member.id)
+            });
+
+            awarded.extend(
+                solves
+                    .into_iter()
+                    .enumerate()
+                    .map(|(rank, (earned_at, _, member))| (member, earned_at, count - rank as u32)),
+            );
+        }
+    }
+
+    awarded
+}
+
+/// Sum up the time each member needed for their stars, counted from the first midnight in the
+/// member's timezone at or after the puzzle unlocked. Stars earned before that count as zero.
+fn rank_by_time(
+    stats: &LeaderboardStats,
+    year: u16,
+    timezone: impl Fn(&User) -> FixedOffset,
+) -> Vec<Ranked<'_>> {
+    stats
+        .members
+        .values()
+        .map(|member| {
+            let timezone = timezone(member);
+            let total = member
+                .all_star_times()
+                .map(|(day, _, earned_at)| {
+                    let start = start_time(year, day, timezone);
+                    (earned_at - start).to_std().unwrap_or_default()
+                })
+                .sum();
+
+            Ranked {
+                member,
+                score: Score::Time {
+                    stars: member.stars,
+                    total,
+                },
+            }
+        })
+        .collect()
+}
+
+/// The first midnight in the given timezone, at or after the puzzle of a day unlocked.
+fn start_time(year: u16, day: DayNumber, timezone: FixedOffset) -> DateTime<Utc> {
+    let unlock = aoc::unlock_time(year, day).with_timezone(&timezone);
+    let midnight = unlock.date().and_hms(0, 0, 0);
+
+    if midnight < unlock {
+        (midnight + chrono::Duration::days(1)).with_timezone(&Utc)
+    } else {
+        midnight.with_timezone(&Utc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::testing::{member, stats, ts};
+
+    const YEAR: u16 = 2020;
+
+    fn day(day: u8) -> DayNumber {
+        DayNumber::new(day).unwrap()
+    }
+
+    fn ids(ranked: &[Ranked<'_>]) -> Vec<String> {
+        ranked
+            .iter()
+            .map(|ranked| ranked.member.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn local_ties_are_broken_by_the_earlier_last_star() {
+        let stats = stats([
+            member("late")
+                .local_score(10)
+                .star(1, Part::One, "2020-12-01T08:00:00Z")
+                .build(),
+            member("early")
+                .local_score(10)
+                .star(1, Part::One, "2020-12-01T06:00:00Z")
+                .build(),
+            member("best")
+                .local_score(20)
+                .star(1, Part::One, "2020-12-01T09:00:00Z")
+                .build(),
+        ]);
+
+        let ranked = rank(&stats, YEAR, ScoringMode::Local, Options::default());
+
+        assert_eq!(ids(&ranked), ["best", "early", "late"]);
+    }
+
+    #[test]
+    fn members_without_stars_come_last_ordered_by_id() {
+        let stats = stats([
+            member("b").build(),
+            member("a").build(),
+            member("c")
+                .star(1, Part::One, "2020-12-01T06:00:00Z")
+                .build(),
+        ]);
+
+        let ranked = rank(&stats, YEAR, ScoringMode::Stars, Options::default());
+
+        assert_eq!(ids(&ranked), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn more_stars_rank_higher_than_less_time() {
+        let stats = stats([
+            member("fast")
+                .star(1, Part::One, "2020-12-01T05:01:00Z")
+                .build(),
+            member("slow")
+                .star(1, Part::One, "2020-12-01T07:00:00Z")
+                .star(1, Part::Two, "2020-12-01T08:00:00Z")
+                .build(),
+            member("faster")
+                .star(1, Part::One, "2020-12-01T05:00:30Z")
+                .build(),
+        ]);
+
+        let ranked = rank(
+            &stats,
+            YEAR,
+            ScoringMode::TimeSinceUnlock,
+            Options::default(),
+        );
+
+        assert_eq!(ids(&ranked), ["slow", "faster", "fast"]);
+        assert_eq!(
+            ranked[0].score,
+            Score::Time {
+                stars: 2,
+                total: Duration::from_secs(5 * 3600),
+            }
+        );
+    }
+
+    #[test]
+    fn start_time_is_the_unlock_in_the_event_timezone() {
+        assert_eq!(
+            start_time(YEAR, day(1), aoc::event_timezone()),
+            ts("2020-12-01T05:00:00Z")
+        );
+    }
+
+    #[test]
+    fn start_time_east_of_the_event_is_the_next_midnight() {
+        // The puzzle unlocks at 06:00 in UTC+1, so the day starts at the following midnight.
+        let offset = FixedOffset::east(3600);
+
+        assert_eq!(start_time(YEAR, day(1), offset), ts("2020-12-01T23:00:00Z"));
+    }
+
+    #[test]
+    fn start_time_west_of_the_event_is_the_next_midnight() {
+        // The puzzle unlocks at 21:00 on the previous evening in UTC-8.
+        let offset = FixedOffset::west(8 * 3600);
+
+        assert_eq!(start_time(YEAR, day(1), offset), ts("2020-12-01T08:00:00Z"));
+    }
+
+    #[test]
+    fn start_time_far_east_is_the_same_evening() {
+        let offset = FixedOffset::east(9 * 3600);
+
+        // Unlocks at 14:00 on the 25th in UTC+9.
+        assert_eq!(
+            start_time(YEAR, day(25), offset),
+            ts("2020-12-25T15:00:00Z")
+        );
+    }
+
+    #[test]
+    fn local_midnight_counts_stars_before_midnight_as_zero() {
+        let offsets = [("east".to_owned(), FixedOffset::east(3600))]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let stats = stats([
+            // Solved before the local midnight, so it took no time at all.
+            member("east")
+                .star(1, Part::One, "2020-12-01T12:00:00Z")
+                .build(),
+            member("event")
+                .star(1, Part::One, "2020-12-01T05:10:00Z")
+                .build(),
+        ]);
+
+        let ranked = rank(
+            &stats,
+            YEAR,
+            ScoringMode::LocalMidnight,
+            Options {
+                utc_offsets: Some(&offsets),
+                voided_days: &[],
+            },
+        );
+
+        assert_eq!(ids(&ranked), ["east", "event"]);
+        assert_eq!(
+            ranked[0].score,
+            Score::Time {
+                stars: 1,
+                total: Duration::ZERO,
+            }
+        );
+    }
+
+    #[test]
+    fn exclude_voided_skips_known_and_configured_days() {
+        let stats = stats([
+            // Only fast on the voided days.
+            member("voided")
+                .star(1, Part::One, "2020-12-01T05:01:00Z")
+                .star(2, Part::One, "2020-12-02T05:01:00Z")
+                .star(3, Part::One, "2020-12-03T06:00:00Z")
+                .build(),
+            member("counted")
+                .star(1, Part::One, "2020-12-01T06:00:00Z")
+                .star(2, Part::One, "2020-12-02T06:00:00Z")
+                .star(3, Part::One, "2020-12-03T05:01:00Z")
+                .build(),
+        ]);
+        let voided_days = [VoidedDay {
+            year: YEAR,
+            day: day(2),
+        }];
+
+        let ranked = rank(
+            &stats,
+            YEAR,
+            ScoringMode::ExcludeVoided,
+            Options {
+                utc_offsets: None,
+                voided_days: &voided_days,
+            },
+        );
+
+        assert_eq!(ids(&ranked), ["counted", "voided"]);
+        assert_eq!(ranked[0].score, Score::Points(2));
+        assert_eq!(ranked[1].score, Score::Points(1));
+    }
+
+    #[test]
+    fn day_points_only_include_members_with_stars() {
+        let stats = stats([
+            member("a")
+                .star(1, Part::One, "2020-12-01T05:01:00Z")
+                .star(1, Part::Two, "2020-12-01T05:03:00Z")
+                .build(),
+            member("b")
+                .star(1, Part::One, "2020-12-01T05:02:00Z")
+                .star(1, Part::Two, "2020-12-01T05:02:30Z")
+                .build(),
+            member("c")
+                .star(2, Part::One, "2020-12-02T05:02:00Z")
+                .build(),
+        ]);
+
+        let points = day_points(&stats, day(1));
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points["a"], 3 + 2);
+        assert_eq!(points["b"], 2 + 3);
+    }
+
+    #[test]
+    fn awarded_points_follow_the_solve_order() {
+        let stats = stats([
+            member("a")
+                .indexed_star(1, Part::One, "2020-12-01T05:02:00Z", 3)
+                .build(),
+            // Same second, but the lower star index was first.
+            member("b")
+                .indexed_star(1, Part::One, "2020-12-01T05:01:00Z", 2)
+                .build(),
+            member("c")
+                .indexed_star(1, Part::One, "2020-12-01T05:01:00Z", 1)
+                .build(),
+        ]);
+
+        let awarded = awarded_points(&stats, |_| true)
+            .into_iter()
+            .map(|(member, _, points)| (member.id.as_str(), points))
+            .collect::<Vec<_>>();
+
+        assert_eq!(awarded, [("c", 3), ("b", 2), ("a", 1)]);
+    }
+
+    #[test]
+    fn awarded_points_are_ordered_by_time() {
+        let stats = stats([member("a")
+            .star(2, Part::One, "2020-12-02T05:01:00Z")
+            .star(1, Part::Two, "2020-12-01T05:02:00Z")
+            .star(1, Part::One, "2020-12-01T05:01:00Z")
+            .build()]);
+
+        let times = awarded_points(&stats, |_| true)
+            .into_iter()
+            .map(|(_, earned_at, _)| earned_at)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            times,
+            [
+                ts("2020-12-01T05:01:00Z"),
+                ts("2020-12-01T05:02:00Z"),
+                ts("2020-12-02T05:01:00Z"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! An HTTP server that serves the leaderboards as JSON API and as simple web dashboard. It uses the
+//! same stored statistics as the Discord commands, so it doesn't cause any extra requests to the
+//! AoC website.
+
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{ensure, Context, Result};
+use axum::extract::{Extension, Path};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::prelude::*;
+use log::{error, info};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::aoc::{self, Client as AocClient, DayNumber, Error as AocError, Part, User};
+use crate::leaderboard::{self, Cached};
+use crate::scoring::{self, Options as ScoringOptions, Score};
+use crate::settings::{AdventOfCode, Board, Http};
+use crate::storage::Store;
+
+/// Time after which the dashboard reloads itself, in seconds.
+const DASHBOARD_REFRESH: u32 = 300;
+/// Maximum time to wait for the readiness endpoint during a health check.
+const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shared state of all request handlers.
+struct State {
+    aoc_client: AocClient,
+    store: Store,
+    aoc_settings: Arc<AdventOfCode>,
+    /// Whether the Discord gateway is connected.
+    gateway: watch::Receiver<bool>,
+}
+
+/// Run the HTTP server on the given address, until the process stops.
+pub async fn run(
+    address: SocketAddr,
+    aoc_client: AocClient,
+    store: Store,
+    aoc_settings: Arc<AdventOfCode>,
+    gateway: watch::Receiver<bool>,
+) -> Result<()> {
+    let state = Arc::new(State {
+        aoc_client,
+        store,
+        aoc_settings,
+        gateway,
+    });
+
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/api/leaderboard/:year/:board", get(api_leaderboard))
+        .route("/dashboard/:year/:board", get(dashboard))
+        .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .layer(Extension(state));
+
+    let server = axum::Server::try_bind(&address)
+        .with_context(|| format!("failed binding HTTP server to {}", address))?;
+
+    info!("HTTP server listening on http://{}", address);
+
+    server
+        .serve(app.into_make_service())
+        .await
+        .context("failed running HTTP server")
+}
+
+/// A leaderboard as returned by the JSON API.
+#[derive(Serialize)]
+struct Leaderboard<'a> {
+    year: u16,
+    board: &'a str,
+    name: &'a str,
+    /// Whether the statistics came from the storage instead of being freshly fetched.
+    cached: bool,
+    members: Vec<Member<'a>>,
+}
+
+/// A single member of a leaderboard, as returned by the JSON API.
+#[derive(Serialize)]
+struct Member<'a> {
+    rank: usize,
+    /// The score in the board's scoring mode, in a human readable format.
+    score: String,
+    #[serde(flatten)]
+    user: &'a User,
+}
+
+/// List all configured leaderboards with links to their dashboards.
+async fn index(Extension(state): Extension<Arc<State>>) -> Html<String> {
+    let year = state.aoc_settings.event_year;
+    let mut body = String::from("<h1>AoC Leaderboards</h1><ul>");
+
+    for board in &state.aoc_settings.boards {
+        write!(
+            body,
+            r#"<li><a href="/dashboard/{year}/{id}">{name}</a> (<a href="/api/leaderboard/{year}/{id}">JSON</a>)</li>"#,
+            year = year,
+            id = escape(&board.id),
+            name = escape(board.display_name()),
+        )
+        .ok();
+    }
+
+    body.push_str("</ul>");
+    Html(page("AoC Leaderboards", &body))
+}
+
+/// Serve the members of a leaderboard as JSON, ranked by the board's scoring mode.
+async fn api_leaderboard(
+    Extension(state): Extension<Arc<State>>,
+    Path((year, board_id)): Path<(u16, String)>,
+) -> Result<Response, ServerError> {
+    let (board, data) = load(&state, year, &board_id).await?;
+    let ranked = rank(&state, board, &data, year);
+
+    Ok(Json(Leaderboard {
+        year,
+        board: &board.id,
+        name: board.display_name(),
+        cached: data.was_cached,
+        members: ranked
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (user, score))| Member {
+                rank: idx + 1,
+                score: score.to_string(),
+                user,
+            })
+            .collect(),
+    })
+    .into_response())
+}
+
+/// Show a leaderboard as web page, with the stars of each member on each day. The page reloads
+/// itself regularly, so it can be left open on a big screen.
+async fn dashboard(
+    Extension(state): Extension<Arc<State>>,
+    Path((year, board_id)): Path<(u16, String)>,
+) -> Result<Html<String>, ServerError> {
+    let (board, data) = load(&state, year, &board_id).await?;
+    let ranked = rank(&state, board, &data, year);
+    let now = Utc::now();
+
+    let mut body = format!(
+        "<h1>AoC Leaderboard {} [{}]</h1><table><tr><th>#</th><th>Name</th><th>Score</th>",
+        year,
+        escape(board.display_name())
+    );
+    for day in DayNumber::all() {
+        write!(body, "<th>{}</th>", day).ok();
+    }
+    body.push_str("</tr>");
+
+    for (idx, (user, score)) in ranked.into_iter().enumerate() {
+        write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td>",
+            idx + 1,
+            escape(user.display_name()),
+            score
+        )
+        .ok();
+
+        for day in DayNumber::all() {
+            let level = user.completion_day_level.get(&day);
+            let class = match (
+                level.and_then(|level| level.get(Part::One)),
+                level.and_then(|level| level.get(Part::Two)),
+            ) {
+                _ if aoc::unlock_time(year, day) > now => "locked",
+                (Some(_), Some(_)) => "gold",
+                (Some(_), None) => "silver",
+                _ => "none",
+            };
+            write!(body, r#"<td class="{}">*</td>"#, class).ok();
+        }
+
+        body.push_str("</tr>");
+    }
+
+    write!(
+        body,
+        "</table><p>Cached: {}, updated {}</p>",
+        data.was_cached,
+        now.format("%Y-%m-%d %H:%M:%S UTC")
+    )
+    .ok();
+
+    Ok(Html(page(&format!("AoC Leaderboard {}", year), &body)))
+}
+
+/// Query the readiness endpoint of a running bot and fail if it isn't ready. This allows health
+/// checks in containers that don't have any other tools like `curl` available.
+pub async fn healthcheck(http: Option<&Http>) -> Result<()> {
+    let http = http.context("the HTTP server is not enabled")?;
+
+    let mut address = http.address;
+    if address.ip().is_unspecified() {
+        address.set_ip(match address {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+
+    let resp = reqwest::Client::builder()
+        .timeout(HEALTHCHECK_TIMEOUT)
+        .build()?
+        .get(format!("http://{}/readyz", address))
+        .send()
+        .await
+        .context("failed reaching the bot")?;
+    let status = resp.status();
+    let body = resp.text().await?;
+
+    ensure!(status.is_success(), "not ready: {}", body);
+
+    println!("{}", body);
+    Ok(())
+}
+
+/// Liveness check, that succeeds as long as the server is running.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness check, that only succeeds while the Discord gateway is connected. Otherwise, the
+/// reason is reported with a `503` status. An expired AoC session doesn't count, as restarting the
+/// bot wouldn't renew it, and the admin gets alerts about it instead.
+async fn readyz(Extension(state): Extension<Arc<State>>) -> (StatusCode, &'static str) {
+    if *state.gateway.borrow() {
+        (StatusCode::OK, "ready")
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Discord gateway not connected",
+        )
+    }
+}
+
+/// Serve the bot's internal metrics in the Prometheus text format.
+async fn metrics() -> Result<String, ServerError> {
+    crate::metrics::encode().map_err(ServerError::Internal)
+}
+
+/// Load the statistics of a configured leaderboard. Other boards can't be accessed, even if the
+/// session cookie would allow it.
+async fn load<'a>(
+    state: &'a State,
+    year: u16,
+    board_id: &str,
+) -> Result<(&'a Board, Cached<aoc::LeaderboardStats>), ServerError> {
+    let board = state
+        .aoc_settings
+        .boards
+        .iter()
+        .find(|board| board.id == board_id)
+        .filter(|_| aoc::is_event_year(year))
+        .ok_or(ServerError::NotFound)?;
+
+    let data = leaderboard::get_aoc_data(&state.aoc_client, &state.store, year, &board.id)
+        .await
+        .map_err(ServerError::Load)?;
+
+    Ok((board, data))
+}
+
+/// Rank the members with the board's scoring mode.
+fn rank<'a>(
+    state: &State,
+    board: &Board,
+    data: &'a aoc::LeaderboardStats,
+    year: u16,
+) -> Vec<(&'a User, Score)> {
+    scoring::rank(
+        data,
+        year,
+        board.scoring,
+        ScoringOptions {
+            utc_offsets: Some(&board.utc_offsets),
+            voided_days: &state.aoc_settings.voided_days,
+        },
+    )
+    .into_iter()
+    .map(|ranked| (ranked.member, ranked.score))
+    .collect()
+}
+
+/// Wrap the body into a full HTML page, with some basic styling.
+fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{refresh}">
+<title>{title}</title>
+<style>
+body {{ background: #0f0f23; color: #cccccc; font-family: "Source Code Pro", monospace; margin: 2em; }}
+a {{ color: #009900; }}
+h1 {{ color: #00cc00; }}
+td, th {{ padding: 0.1em 0.4em; text-align: left; }}
+.gold {{ color: #ffff66; }}
+.silver {{ color: #9999cc; }}
+.none {{ color: #333340; }}
+.locked {{ color: transparent; }}
+</style>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        refresh = DASHBOARD_REFRESH,
+        title = escape(title),
+        body = body
+    )
+}
+
+/// Escape text for safe use in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Errors while handling a request, that are turned into a matching response.
+enum ServerError {
+    /// The year or board isn't available.
+    NotFound,
+    /// Loading the statistics failed.
+    Load(anyhow::Error),
+    /// Any other failure.
+    Internal(anyhow::Error),
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let error = match self {
+            Self::NotFound => return StatusCode::NOT_FOUND.into_response(),
+            Self::Load(error) => error,
+            Self::Internal(error) => {
+                error!("failed handling request: {:?}", error);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        error!("failed loading leaderboard: {:?}", error);
+
+        match error.downcast_ref::<AocError>() {
+            Some(AocError::Throttled { retry_in }) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, retry_in.as_secs().to_string())],
+            )
+                .into_response(),
+            Some(AocError::BoardNotFound) => StatusCode::NOT_FOUND.into_response(),
+            Some(_) => StatusCode::BAD_GATEWAY.into_response(),
+            None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
@@ -1,16 +1,24 @@
 //! Authentication and logging settings for the bot.
 
+use std::collections::HashMap;
 use std::env;
 use std::io::ErrorKind;
+use std::net::SocketAddr;
 use std::num::NonZeroU64;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use anyhow::{anyhow, Context, Result};
-use serde::de::DeserializeOwned;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use chrono::FixedOffset;
+use serde::de::{self, DeserializeOwned, Deserializer};
 use serde::Deserialize;
 use simplelog::LevelFilter;
 use tokio::fs;
 
+use crate::aoc::{DEFAULT_BASE_URL, MIN_REQUEST_INTERVAL};
+use crate::roles::RoleRule;
+use crate::scoring::{ScoringMode, VoidedDay};
+
 /// Main structure that holds all the settings of this bot.
 #[derive(Deserialize)]
 pub struct Settings {
@@ -20,6 +28,10 @@ pub struct Settings {
     pub aoc: AdventOfCode,
     /// Discord related settings.
     pub discord: Discord,
+    /// Persistent storage settings.
+    pub storage: Storage,
+    /// Embedded HTTP server settings, which is only started if this is set.
+    pub http: Option<Http>,
 }
 
 /// All configuration for the logging of the bot, including different logging backends like a file
@@ -64,13 +76,80 @@ pub struct FileLogger {
 /// All settings regarding the Advent of Code API.
 #[derive(Deserialize)]
 pub struct AdventOfCode {
-    /// The leaderboard that is queried for current rankings and statistics.
+    /// A single leaderboard that is used in all channels, for simple setups. It is merged into the
+    /// [`Self::boards`] while loading the settings.
+    #[serde(default)]
     pub board_id: String,
+    /// The leaderboards that are queried for current rankings and statistics.
+    #[serde(default)]
+    pub boards: Vec<Board>,
     /// A session cookie to authenticate against the API. This is usually manually extracted with
     /// browser dev tools after logging into the website.
     pub session_cookie: String,
     /// The current event that is being tracked.
     pub event_year: u16,
+    /// Address of the AoC website, which can be changed to use a mock server instead.
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// Minimum time between two requests to the same AoC API endpoint. Can't be lower than 15
+    /// minutes, as asked by the AoC website owners.
+    #[serde(
+        default = "default_request_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub request_interval: Duration,
+    /// Days that don't count for the [`ScoringMode::ExcludeVoided`] scoring, in addition to the
+    /// days that were voided by the AoC website.
+    #[serde(default)]
+    pub voided_days: Vec<VoidedDay>,
+}
+
+fn default_base_url() -> String {
+    DEFAULT_BASE_URL.to_owned()
+}
+
+fn default_request_interval() -> Duration {
+    MIN_REQUEST_INTERVAL
+}
+
+impl AdventOfCode {
+    /// Find the leaderboard for a Discord channel. That is the board the channel is bound to, or
+    /// otherwise the first board that isn't bound to any specific channels.
+    pub fn board_for(&self, channel_id: NonZeroU64) -> Option<&Board> {
+        self.boards
+            .iter()
+            .find(|board| board.channels.contains(&channel_id))
+            .or_else(|| self.boards.iter().find(|board| board.channels.is_empty()))
+    }
+}
+
+/// A single private leaderboard and the Discord channels it is used in.
+#[derive(Clone, Deserialize)]
+pub struct Board {
+    /// ID of the private leaderboard.
+    pub id: String,
+    /// Optional display name, used instead of the ID in messages.
+    pub name: Option<String>,
+    /// Discord channels that use this leaderboard. If empty, it is used in all channels that
+    /// aren't bound to any other board.
+    #[serde(default)]
+    pub channels: Vec<NonZeroU64>,
+    /// Discord channel to post live updates to, like new stars or members.
+    pub feed_channel_id: Option<NonZeroU64>,
+    /// How members are ranked, unless a command asks for another mode.
+    #[serde(default)]
+    pub scoring: ScoringMode,
+    /// Timezones of the members by their AoC user ID, as offset to UTC like `+01:00`. Used for
+    /// the [`ScoringMode::LocalMidnight`] scoring.
+    #[serde(default, deserialize_with = "deserialize_utc_offsets")]
+    pub utc_offsets: HashMap<String, FixedOffset>,
+}
+
+impl Board {
+    /// Name of the board for display purposes.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
 }
 
 /// Configuration for the Discord API.
@@ -80,6 +159,44 @@ pub struct Discord {
     pub bot_token: String,
     #[serde(default)]
     pub schedule: Option<Schedule>,
+    /// Where to send alerts about problems that need the attention of the bot's admin.
+    #[serde(default)]
+    pub alerts: Option<Alerts>,
+    /// Channel to announce each puzzle in, as soon as it unlocks.
+    pub unlock_channel_id: Option<NonZeroU64>,
+    /// How the podium of the top 3 members is shown.
+    #[serde(default)]
+    pub podium: PodiumStyle,
+    /// Roles that are granted to linked members depending on their progress.
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// Whether to also handle the legacy commands with a `!` prefix, in addition to the slash
+    /// commands. This needs the privileged message content intent.
+    #[serde(default)]
+    pub prefix_commands: bool,
+}
+
+/// A Discord role that linked members have as long as they fulfill its rule.
+#[derive(Clone, Deserialize)]
+pub struct Role {
+    pub guild_id: NonZeroU64,
+    pub role_id: NonZeroU64,
+    /// ID of the leaderboard to check the rule against. Defaults to the first leaderboard.
+    pub board: Option<String>,
+    #[serde(flatten)]
+    pub rule: RoleRule,
+}
+
+/// The different ways to show the podium of the top 3 members.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PodiumStyle {
+    /// An image, which needs the permission to attach files. Falls back to [`Self::Ascii`] if the
+    /// permission is missing.
+    #[default]
+    Image,
+    /// ASCII art in a code block.
+    Ascii,
 }
 
 #[derive(Deserialize)]
@@ -88,6 +205,59 @@ pub struct Schedule {
     pub channel_id: NonZeroU64,
 }
 
+/// Alerting settings, to notify the bot's admin about problems like an expired AoC session. At least
+/// one of the channel or user must be set.
+#[derive(Deserialize)]
+pub struct Alerts {
+    /// Channel to post alerts in.
+    pub channel_id: Option<NonZeroU64>,
+    /// User to send alerts to as direct message.
+    pub user_id: Option<NonZeroU64>,
+    /// Interval to repeat an alert at, as long as the problem persists.
+    #[serde(
+        default = "default_alert_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+}
+
+fn default_alert_interval() -> Duration {
+    Duration::from_secs(6 * 60 * 60)
+}
+
+impl Default for Alerts {
+    fn default() -> Self {
+        Self {
+            channel_id: None,
+            user_id: None,
+            interval: default_alert_interval(),
+        }
+    }
+}
+
+/// Settings for the persistent storage of the bot.
+#[derive(Deserialize)]
+pub struct Storage {
+    /// Location of the database file, which is created if it doesn't exist yet.
+    pub path: PathBuf,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("storage/aoc_bot.db"),
+        }
+    }
+}
+
+/// Settings for the embedded HTTP server, that provides the leaderboards as JSON API and web
+/// dashboard.
+#[derive(Deserialize)]
+pub struct Http {
+    /// Address and port to listen on, like `0.0.0.0:8080`.
+    pub address: SocketAddr,
+}
+
 /// A wrapper for the [LevelFilter] that allows to use it in [serde], as it doesn't provide support
 /// for it out of the box.
 #[derive(Deserialize)]
@@ -100,12 +270,59 @@ enum SerdeLevelFilter {
     Trace,
 }
 
+/// Deserialize a human readable duration like `15m` or `1h 30m`.
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    humantime::parse_duration(&value).map_err(de::Error::custom)
+}
+
+/// Deserialize a map of UTC offsets in the form of `+01:00`, `-05:30` or just `+1`.
+fn deserialize_utc_offsets<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, FixedOffset>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(id, offset)| {
+            parse_utc_offset(&offset)
+                .map(|offset| (id, offset))
+                .ok_or_else(|| de::Error::custom(format!("invalid UTC offset `{}`", offset)))
+        })
+        .collect()
+}
+
+fn parse_utc_offset(value: &str) -> Option<FixedOffset> {
+    let (sign, value) = if let Some(value) = value.strip_prefix('+') {
+        (1, value)
+    } else {
+        (-1, value.strip_prefix('-')?)
+    };
+    let (hours, minutes) = value.split_once(':').unwrap_or((value, "0"));
+    let hours = hours.parse::<u32>().ok()?;
+    let minutes = minutes.parse::<u32>().ok()?;
+
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60) as i32)
+}
+
 /// An intermediate structure for the authentication related settings that allows to parse them
 /// separately and merge them into a single [Settings] structure later.
 #[derive(Deserialize)]
 struct Auth {
     aoc: AdventOfCode,
     discord: Discord,
+    #[serde(default)]
+    storage: Storage,
+    #[serde(default)]
+    http: Option<Http>,
 }
 
 impl Default for Auth {
@@ -113,13 +330,24 @@ impl Default for Auth {
         Self {
             aoc: AdventOfCode {
                 board_id: String::new(),
+                boards: Vec::new(),
                 session_cookie: String::new(),
                 event_year: 2021,
+                base_url: default_base_url(),
+                request_interval: default_request_interval(),
+                voided_days: Vec::new(),
             },
             discord: Discord {
                 bot_token: String::new(),
                 schedule: None,
+                alerts: None,
+                unlock_channel_id: None,
+                podium: PodiumStyle::default(),
+                roles: Vec::new(),
+                prefix_commands: false,
             },
+            storage: Storage::default(),
+            http: None,
         }
     }
 }
@@ -133,16 +361,30 @@ impl Settings {
         let Auth {
             mut aoc,
             mut discord,
+            mut storage,
+            mut http,
         } = load_toml("config/auth.toml").await?;
 
         load_logging_envs(&mut logging)?;
         load_aoc_envs(&mut aoc)?;
+        merge_legacy_board(&mut aoc);
         load_discord_envs(&mut discord)?;
+        load_storage_envs(&mut storage);
+        load_http_envs(&mut http)?;
+
+        if let Some(alerts) = &discord.alerts {
+            ensure!(
+                alerts.channel_id.is_some() || alerts.user_id.is_some(),
+                "Discord alerts need a channel or user to send them to"
+            );
+        }
 
         Ok(Self {
             logging,
             aoc,
             discord,
+            storage,
+            http,
         })
     }
 }
@@ -188,9 +430,33 @@ fn load_aoc_envs(aoc: &mut AdventOfCode) -> Result<()> {
             .context("Failed to parse AOC event year")?;
     }
 
+    if let Ok(base_url) = env::var("AOC_BASE_URL") {
+        aoc.base_url = base_url;
+    }
+
+    if let Ok(request_interval) = env::var("AOC_REQUEST_INTERVAL") {
+        aoc.request_interval = humantime::parse_duration(&request_interval)
+            .context("Failed to parse AOC request interval")?;
+    }
+
     Ok(())
 }
 
+/// Move the single `board_id` into the list of boards, so it can be handled the same way as all
+/// other boards.
+fn merge_legacy_board(aoc: &mut AdventOfCode) {
+    if !aoc.board_id.is_empty() {
+        aoc.boards.push(Board {
+            id: std::mem::take(&mut aoc.board_id),
+            name: None,
+            channels: Vec::new(),
+            feed_channel_id: None,
+            scoring: ScoringMode::default(),
+            utc_offsets: HashMap::new(),
+        });
+    }
+}
+
 /// Overwrite Discord settings with any provided env vars.
 fn load_discord_envs(discord: &mut Discord) -> Result<()> {
     if let Ok(bot_token) = env::var("DISCORD_BOT_TOKEN") {
@@ -214,6 +480,73 @@ fn load_discord_envs(discord: &mut Discord) -> Result<()> {
         });
     }
 
+    if let Ok(channel_id) = env::var("DISCORD_ALERTS_CHANNEL_ID") {
+        discord
+            .alerts
+            .get_or_insert_with(Alerts::default)
+            .channel_id = Some(
+            channel_id
+                .parse()
+                .context("Failed to parse Discord alerts channel ID")?,
+        );
+    }
+
+    if let Ok(user_id) = env::var("DISCORD_ALERTS_USER_ID") {
+        discord.alerts.get_or_insert_with(Alerts::default).user_id = Some(
+            user_id
+                .parse()
+                .context("Failed to parse Discord alerts user ID")?,
+        );
+    }
+
+    if let Ok(channel_id) = env::var("DISCORD_UNLOCK_CHANNEL_ID") {
+        discord.unlock_channel_id = Some(
+            channel_id
+                .parse()
+                .context("Failed to parse Discord unlock channel ID")?,
+        );
+    }
+
+    if let Ok(podium) = env::var("DISCORD_PODIUM") {
+        discord.podium = match podium.as_str() {
+            "image" => PodiumStyle::Image,
+            "ascii" => PodiumStyle::Ascii,
+            _ => bail!("Invalid Discord podium style `{}`", podium),
+        };
+    }
+
+    if let Ok(prefix_commands) = env::var("DISCORD_PREFIX_COMMANDS") {
+        discord.prefix_commands = prefix_commands
+            .parse()
+            .context("Failed to parse Discord prefix commands flag")?;
+    }
+
+    if let (Some(alerts), Ok(interval)) = (&mut discord.alerts, env::var("DISCORD_ALERTS_INTERVAL"))
+    {
+        alerts.interval = humantime::parse_duration(&interval)
+            .context("Failed to parse Discord alerts interval")?;
+    }
+
+    Ok(())
+}
+
+/// Overwrite storage settings with any provided env vars.
+fn load_storage_envs(storage: &mut Storage) {
+    if let Ok(path) = env::var("STORAGE_PATH") {
+        storage.path = PathBuf::from(path);
+    }
+}
+
+/// Overwrite HTTP server settings with any provided env vars.
+fn load_http_envs(http: &mut Option<Http>) -> Result<()> {
+    if let Ok(address) = env::var("HTTP_ADDRESS") {
+        *http = Some(Http {
+            address: address
+                .parse()
+                .context("Failed to parse HTTP server address")?,
+        });
+    }
+
     Ok(())
 }
 
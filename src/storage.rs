@@ -0,0 +1,581 @@
+//! Persistent storage for the bot, backed by an embedded SQLite database. It keeps a history of all
+//! leaderboard statistics that were fetched from the AoC API, so they survive restarts, as well as
+//! the links between Discord users and AoC members.
+
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::aoc::{LeaderboardStats, RequestTimes};
+
+/// Database schema that is applied every time the storage is opened. All statements must be
+/// idempotent.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    id         INTEGER PRIMARY KEY,
+    board_id   TEXT    NOT NULL,
+    year       INTEGER NOT NULL,
+    fetched_at INTEGER NOT NULL,
+    stats      TEXT    NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS snapshots_board_year ON snapshots (board_id, year, fetched_at);
+
+CREATE TABLE IF NOT EXISTS links (
+    discord_id INTEGER PRIMARY KEY,
+    aoc_id     TEXT    NOT NULL UNIQUE,
+    linked_at  INTEGER NOT NULL,
+    confirmed  INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS requests (
+    endpoint     TEXT    PRIMARY KEY,
+    requested_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS granted_roles (
+    guild_id INTEGER NOT NULL,
+    user_id  INTEGER NOT NULL,
+    role_id  INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id, role_id)
+);
+
+CREATE TABLE IF NOT EXISTS announced (
+    board_id   TEXT    NOT NULL,
+    year       INTEGER NOT NULL,
+    fetched_at INTEGER NOT NULL,
+    stats      TEXT    NOT NULL,
+    PRIMARY KEY (board_id, year)
+);
+";
+
+/// A single fetch of leaderboard statistics, together with the time it was retrieved.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub board_id: String,
+    pub year: u16,
+    pub fetched_at: DateTime<Utc>,
+    pub stats: LeaderboardStats,
+}
+
+/// The Discord user that an AoC member is linked to.
+#[derive(Clone, Copy, Debug)]
+pub struct Link {
+    pub discord_id: NonZeroU64,
+    /// Whether an admin confirmed that the Discord user really is the AoC member. Users can link
+    /// themselves to any member, so only confirmed links are trusted with roles.
+    pub confirmed: bool,
+}
+
+/// A Discord role that the bot granted to a user, so it can be revoked again once the user isn't
+/// linked anymore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grant {
+    pub guild_id: NonZeroU64,
+    pub user_id: NonZeroU64,
+    pub role_id: NonZeroU64,
+}
+
+/// Handle to the storage database. It can be cheaply cloned and shared between tasks.
+#[derive(Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    /// Open the database at the given location, creating the file and any missing parent folders
+    /// if needed.
+    pub async fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.with_context(|| {
+                format!("failed creating storage folder '{}'", parent.display())
+            })?;
+        }
+
+        let path = path.to_owned();
+        let conn = tokio::task::spawn_blocking(move || -> Result<Connection> {
+            let conn = Connection::open(&path)
+                .with_context(|| format!("failed opening database at '{}'", path.display()))?;
+            conn.execute_batch(SCHEMA)
+                .context("failed applying database schema")?;
+            Ok(conn)
+        })
+        .await??;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Persist freshly fetched leaderboard statistics. If they didn't change since the latest
+    /// snapshot, only its fetch time is updated instead, so the database doesn't grow while nobody
+    /// makes progress.
+    pub async fn save_snapshot(
+        &self,
+        year: u16,
+        board_id: &str,
+        fetched_at: DateTime<Utc>,
+        stats: &LeaderboardStats,
+    ) -> Result<()> {
+        let board_id = board_id.to_owned();
+        // Going through a JSON value sorts the members by ID, so unchanged statistics always result
+        // in the same text.
+        let stats = serde_json::to_string(&serde_json::to_value(stats)?)?;
+
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                "UPDATE snapshots SET fetched_at = ?3
+                WHERE id = (
+                    SELECT id FROM snapshots WHERE board_id = ?1 AND year = ?2
+                    ORDER BY fetched_at DESC LIMIT 1
+                ) AND stats = ?4",
+                params![board_id, year, fetched_at.timestamp(), stats],
+            )?;
+
+            if updated == 0 {
+                conn.execute(
+                    "INSERT INTO snapshots (board_id, year, fetched_at, stats)
+                    VALUES (?1, ?2, ?3, ?4)",
+                    params![board_id, year, fetched_at.timestamp(), stats],
+                )?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the most recent snapshot of a leaderboard, if it was ever fetched.
+    pub async fn latest_snapshot(&self, year: u16, board_id: &str) -> Result<Option<Snapshot>> {
+        let board_id = board_id.to_owned();
+
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT fetched_at, stats FROM snapshots
+                WHERE board_id = ?1 AND year = ?2
+                ORDER BY fetched_at DESC LIMIT 1",
+                params![board_id, year],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?
+            .map(|(fetched_at, stats)| to_snapshot(board_id, year, fetched_at, &stats))
+            .transpose()
+        })
+        .await
+    }
+
+    /// Get the snapshot of a leaderboard that the live feed last announced, if any.
+    pub async fn announced_snapshot(&self, year: u16, board_id: &str) -> Result<Option<Snapshot>> {
+        let board_id = board_id.to_owned();
+
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT fetched_at, stats FROM announced WHERE board_id = ?1 AND year = ?2",
+                params![board_id, year],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?
+            .map(|(fetched_at, stats)| to_snapshot(board_id, year, fetched_at, &stats))
+            .transpose()
+        })
+        .await
+    }
+
+    /// Remember the snapshot of a leaderboard that the live feed announced all changes up to.
+    pub async fn save_announced(
+        &self,
+        year: u16,
+        board_id: &str,
+        fetched_at: DateTime<Utc>,
+        stats: &LeaderboardStats,
+    ) -> Result<()> {
+        let board_id = board_id.to_owned();
+        let stats = serde_json::to_string(stats)?;
+
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO announced (board_id, year, fetched_at, stats) VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (board_id, year) DO UPDATE
+                SET fetched_at = excluded.fetched_at, stats = excluded.stats",
+                params![board_id, year, fetched_at.timestamp(), stats],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Replace the stored last request times of the AoC endpoints.
+    pub async fn save_request_times(&self, times: RequestTimes) -> Result<()> {
+        self.with_conn(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM requests", [])?;
+
+            for (endpoint, requested_at) in times {
+                tx.execute(
+                    "INSERT INTO requests (endpoint, requested_at) VALUES (?1, ?2)",
+                    params![endpoint, requested_at.timestamp()],
+                )?;
+            }
+
+            tx.commit().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Get the last request time of each AoC endpoint, as saved before.
+    pub async fn request_times(&self) -> Result<RequestTimes> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT endpoint, requested_at FROM requests")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?;
+
+            rows.map(|row| {
+                let (endpoint, requested_at) = row?;
+                Ok((endpoint, Utc.timestamp(requested_at, 0)))
+            })
+            .collect()
+        })
+        .await
+    }
+
+    /// Link a Discord user to an AoC member, replacing any previous link of the user. Each AoC
+    /// member can only be linked to a single Discord user. Linking to another member than before
+    /// always needs a new confirmation.
+    pub async fn link(&self, discord_id: NonZeroU64, aoc_id: &str) -> Result<()> {
+        let aoc_id = aoc_id.to_owned();
+
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO links (discord_id, aoc_id, linked_at) VALUES (?1, ?2, ?3)
+                ON CONFLICT (discord_id) DO UPDATE
+                SET aoc_id = excluded.aoc_id, linked_at = excluded.linked_at,
+                    confirmed = confirmed AND aoc_id = excluded.aoc_id",
+                params![discord_id.get() as i64, aoc_id, Utc::now().timestamp()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Confirm the link of a Discord user and return the AoC member ID it is linked to, if any.
+    pub async fn confirm_link(&self, discord_id: NonZeroU64) -> Result<Option<String>> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "UPDATE links SET confirmed = 1 WHERE discord_id = ?1 RETURNING aoc_id",
+                params![discord_id.get() as i64],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Link a Discord user to an AoC member and confirm the link right away, as done by an admin.
+    /// Another user that was linked to the member loses their link and is returned.
+    pub async fn assign_link(
+        &self,
+        discord_id: NonZeroU64,
+        aoc_id: &str,
+    ) -> Result<Option<NonZeroU64>> {
+        let aoc_id = aoc_id.to_owned();
+
+        self.with_conn(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            let previous = tx
+                .query_row(
+                    "DELETE FROM links WHERE aoc_id = ?1 AND discord_id != ?2 RETURNING discord_id",
+                    params![aoc_id, discord_id.get() as i64],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()?;
+            tx.execute(
+                "INSERT INTO links (discord_id, aoc_id, linked_at, confirmed) VALUES (?1, ?2, ?3, 1)
+                ON CONFLICT (discord_id) DO UPDATE
+                SET aoc_id = excluded.aoc_id, linked_at = excluded.linked_at, confirmed = 1",
+                params![discord_id.get() as i64, aoc_id, Utc::now().timestamp()],
+            )?;
+            tx.commit()?;
+
+            Ok(previous.and_then(|id| NonZeroU64::new(id as u64)))
+        })
+        .await
+    }
+
+    /// Remove the link of a Discord user and return the AoC member ID it was linked to, if any.
+    pub async fn unlink(&self, discord_id: NonZeroU64) -> Result<Option<String>> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "DELETE FROM links WHERE discord_id = ?1 RETURNING aoc_id",
+                params![discord_id.get() as i64],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Get all links, by the linked AoC member ID.
+    pub async fn links(&self) -> Result<HashMap<String, Link>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT aoc_id, discord_id, confirmed FROM links")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            })?;
+
+            rows.map(|row| {
+                let (aoc_id, discord_id, confirmed) = row?;
+                let discord_id = NonZeroU64::new(discord_id as u64)
+                    .context("invalid Discord user ID in storage")?;
+                Ok((
+                    aoc_id,
+                    Link {
+                        discord_id,
+                        confirmed,
+                    },
+                ))
+            })
+            .collect()
+        })
+        .await
+    }
+
+    /// Remember that the bot granted a role to a user.
+    pub async fn record_grant(&self, grant: Grant) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO granted_roles (guild_id, user_id, role_id)
+                VALUES (?1, ?2, ?3)",
+                params![
+                    grant.guild_id.get() as i64,
+                    grant.user_id.get() as i64,
+                    grant.role_id.get() as i64
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Forget a granted role, after it was revoked.
+    pub async fn remove_grant(&self, grant: Grant) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM granted_roles WHERE guild_id = ?1 AND user_id = ?2 AND role_id = ?3",
+                params![
+                    grant.guild_id.get() as i64,
+                    grant.user_id.get() as i64,
+                    grant.role_id.get() as i64
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get all roles the bot granted and didn't revoke yet.
+    pub async fn grants(&self) -> Result<Vec<Grant>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT guild_id, user_id, role_id FROM granted_roles")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })?;
+
+            rows.map(|row| {
+                let (guild_id, user_id, role_id) = row?;
+                let id = |id: i64| {
+                    NonZeroU64::new(id as u64).context("invalid Discord ID in granted roles")
+                };
+
+                Ok(Grant {
+                    guild_id: id(guild_id)?,
+                    user_id: id(user_id)?,
+                    role_id: id(role_id)?,
+                })
+            })
+            .collect()
+        })
+        .await
+    }
+
+    /// Run a blocking database operation on a separate thread, to not block the async runtime.
+    async fn with_conn<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| anyhow!("storage connection poisoned"))?;
+            f(&conn)
+        })
+        .await?
+    }
+}
+
+/// Convert the raw database values into a [`Snapshot`].
+fn to_snapshot(board_id: String, year: u16, fetched_at: i64, stats: &str) -> Result<Snapshot> {
+    Ok(Snapshot {
+        board_id,
+        year,
+        fetched_at: Utc.timestamp(fetched_at, 0),
+        stats: serde_json::from_str(stats).context("invalid snapshot in storage")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::testing::{member, stats, ts};
+    use crate::aoc::Part;
+
+    /// Open a new, empty store in a temporary file.
+    async fn open(name: &str) -> Store {
+        let path =
+            std::env::temp_dir().join(format!("aoc_bot-test-{}-{}.db", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+
+        Store::open(&path).await.unwrap()
+    }
+
+    async fn snapshot_count(store: &Store) -> i64 {
+        store
+            .with_conn(|conn| {
+                conn.query_row("SELECT COUNT(*) FROM snapshots", [], |row| row.get(0))
+                    .map_err(Into::into)
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn unchanged_snapshots_only_update_the_fetch_time() {
+        let store = open("unchanged").await;
+        let board = stats([
+            member("a").build(),
+            member("b").build(),
+            member("c").build(),
+        ]);
+        let later = ts("2020-12-01T06:00:00Z");
+
+        store
+            .save_snapshot(2020, "1", ts("2020-12-01T05:00:00Z"), &board)
+            .await
+            .unwrap();
+        // Deserializing creates a new map, which usually iterates the members in another order.
+        let reloaded = serde_json::from_str(&serde_json::to_string(&board).unwrap()).unwrap();
+        store
+            .save_snapshot(2020, "1", later, &reloaded)
+            .await
+            .unwrap();
+
+        let latest = store.latest_snapshot(2020, "1").await.unwrap().unwrap();
+        assert_eq!(latest.fetched_at, later);
+        assert_eq!(snapshot_count(&store).await, 1);
+    }
+
+    #[tokio::test]
+    async fn request_times_are_replaced() {
+        let store = open("requests").await;
+        let first = [
+            ("a".to_owned(), ts("2020-12-01T05:00:00Z")),
+            ("b".to_owned(), ts("2020-12-01T05:00:00Z")),
+        ];
+        let second = [("a".to_owned(), ts("2020-12-01T05:15:00Z"))];
+
+        store
+            .save_request_times(first.into_iter().collect())
+            .await
+            .unwrap();
+        store
+            .save_request_times(second.clone().into_iter().collect())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.request_times().await.unwrap(),
+            second.into_iter().collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn changed_snapshots_are_kept() {
+        let store = open("changed").await;
+        let before = stats([member("a").build()]);
+        let after = stats([member("a")
+            .star(1, Part::One, "2020-12-01T05:10:00Z")
+            .build()]);
+
+        store
+            .save_snapshot(2020, "1", ts("2020-12-01T05:00:00Z"), &before)
+            .await
+            .unwrap();
+        store
+            .save_snapshot(2020, "1", ts("2020-12-01T06:00:00Z"), &after)
+            .await
+            .unwrap();
+        store
+            .save_snapshot(2020, "2", ts("2020-12-01T06:00:00Z"), &after)
+            .await
+            .unwrap();
+
+        let latest = store.latest_snapshot(2020, "1").await.unwrap().unwrap();
+        assert_eq!(latest.stats.members["a"].stars, 1);
+        assert_eq!(snapshot_count(&store).await, 3);
+    }
+
+    #[tokio::test]
+    async fn assigned_links_are_taken_over_and_confirmed() {
+        let store = open("assign-link").await;
+        let ada = NonZeroU64::new(1).unwrap();
+        let mallory = NonZeroU64::new(2).unwrap();
+
+        store.link(mallory, "100").await.unwrap();
+        store.link(ada, "200").await.unwrap();
+
+        assert_eq!(store.assign_link(ada, "100").await.unwrap(), Some(mallory));
+
+        let links = store.links().await.unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links["100"].discord_id, ada);
+        assert!(links["100"].confirmed);
+
+        // Assigning the same member again doesn't take it from anyone.
+        assert_eq!(store.assign_link(ada, "100").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn grants_are_recorded_once_until_removed() {
+        let store = open("grants").await;
+        let grant = Grant {
+            guild_id: NonZeroU64::new(1).unwrap(),
+            user_id: NonZeroU64::new(2).unwrap(),
+            role_id: NonZeroU64::new(3).unwrap(),
+        };
+
+        store.record_grant(grant).await.unwrap();
+        store.record_grant(grant).await.unwrap();
+        assert_eq!(store.grants().await.unwrap(), [grant]);
+
+        store.remove_grant(grant).await.unwrap();
+        assert!(store.grants().await.unwrap().is_empty());
+    }
+}